@@ -0,0 +1,16 @@
+// Compiles the Intcode input of the search-heavy d07 to Rust; see
+// intcode::aot.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+fn main() {
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let input = "d07.in";
+    println!("cargo:rerun-if-changed={}", input);
+    let text = fs::read_to_string(input).unwrap();
+    let program: Vec<i128> = intcode::parse_program(&text).unwrap();
+    let code = intcode::aot::compile(&program, input);
+    fs::write(Path::new(&out_dir).join("d07.rs"), code).unwrap();
+}
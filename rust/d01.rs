@@ -4,7 +4,7 @@ use std::io::prelude::*;
 
 
 fn calculate_fuel(mass: i32) -> i32 {
-    return mass / 3 - 2;
+    mass / 3 - 2
 }
 
 fn main() -> std::io::Result<()> {
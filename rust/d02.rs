@@ -1,60 +1,36 @@
 use std::fs;
 
-const ADD_OP: u32 = 1;
-const MUL_OP: u32 = 2;
-const END_OP: u32 = 99;
+use intcode::symbolic::{Goal, Problem};
+use intcode::{Cpu, Error};
 
-const WANTED_OUTPUT: u32 = 19690720;
+const WANTED_OUTPUT: i128 = 19690720;
 
-fn apply_op(op: u32, a: u32, b: u32) -> u32 {
-    match op {
-	ADD_OP => a + b,
-	MUL_OP => a * b,
-	_ => panic!("unknown op"),
-    }
-}
-
-fn process(mem: &mut Vec<u32>) {
-    let mut pc: usize = 0;
-    while mem[pc] != END_OP {
-	let opcode = mem[pc];
-	let a = mem[mem[pc + 1] as usize];
-	let b = mem[mem[pc + 2] as usize];
-	let res_loc = mem[pc + 3];
-	mem[res_loc as usize] = apply_op(opcode, a, b);
-	pc += 4;
-    }
+fn get_output(noun: i128, verb: i128, init: &Cpu) -> Result<i128, Error> {
+    let mut cpu = init.snapshot();
+    cpu.mem[1] = noun;
+    cpu.mem[2] = verb;
+    cpu.run()?;
+    Ok(cpu.mem[0])
 }
 
-fn get_output(noun: u32, verb: u32, init_mem: &Vec<u32>) -> u32 {
-    let mut mem = init_mem.clone();
-    mem[1] = noun;
-    mem[2] = verb;
-    process(&mut mem);
-    mem[0]
-}
-
-fn find_inputs_for(out: u32, init_mem: &Vec<u32>) -> (u32, u32) {
-    let mut input: (u32, u32) = (0, 0);
-    'outer: for i in 0..100 {
-	for j in 0..100 {
-	    if get_output(i, j, init_mem) == out {
-		input = (i, j);
-		break 'outer;
-	    }
-	}
+fn find_inputs_for(out: i128, program: &[i128]) -> (i128, i128) {
+    let mut problem = Problem::new(program);
+    problem.mem(1, 0..=99).mem(2, 0..=99);
+    match problem.solve(Goal::Mem(0), out) {
+	Some(solution) => (solution.values[0], solution.values[1]),
+	None => (0, 0),
     }
-    input
 }
 
 fn main() -> std::io::Result<()> {
     let instructions = fs::read_to_string("d02.in")?;
-    let mut instructions: Vec<u32> = instructions.trim()
-	.split(",")
-	.map(|x| x.parse().expect("failed to parse number"))
-	.collect();
+    let instructions = intcode::parse_program(&instructions)
+	.expect("failed to parse number");
 
-    let ans1 = get_output(12, 2, &instructions);
+    let init = Cpu::new(&instructions);
+
+    let ans1 = get_output(12, 2, &init)
+	.expect("program failed");
     println!("Part 1: {}", ans1);
 
     let (noun, verb) = find_inputs_for(WANTED_OUTPUT, &instructions);
@@ -63,4 +39,3 @@ fn main() -> std::io::Result<()> {
     
     Ok(())
 }
-
@@ -6,7 +6,7 @@ use std::collections::HashSet;
 type Point = (i32, i32);
 
 fn manhattan_distance(p: &Point) -> u32 {
-    p.0.abs() as u32 + p.1.abs() as u32
+    p.0.unsigned_abs() + p.1.unsigned_abs()
 }
 
 enum Direction {
@@ -100,7 +100,7 @@ fn find_closest_intersection(w: &Wire, v: &Wire) -> Option<u32> {
     let w_pts = w.get_points();
     let v_pts = v.get_points();
     let intersections = w_pts.intersection(&v_pts);
-    intersections.map(|p| manhattan_distance(p))
+    intersections.map(manhattan_distance)
 	.min()
 }
 
@@ -0,0 +1,19 @@
+// Fuzzes d07.in compiled to Rust by build.rs against the interpreter.
+
+mod compiled {
+    include!(concat!(env!("OUT_DIR"), "/d07.rs"));
+}
+
+use intcode::fuzz::Fuzzer;
+
+#[test]
+fn compiled_d07_matches_cpu() {
+    for seed in [1, 2, 3] {
+	let fuzzer = Fuzzer::new(seed);
+	// phases, including bad ones, then signals
+	let mismatch = fuzzer.run_compiled(compiled::machine, 4, -2..=12, 2000);
+	assert!(mismatch.is_none(), "{}", mismatch.unwrap());
+	let mismatch = fuzzer.run_compiled(compiled::machine, 3, -(1 << 100)..=1 << 100, 500);
+	assert!(mismatch.is_none(), "{}", mismatch.unwrap());
+    }
+}
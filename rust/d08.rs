@@ -7,8 +7,8 @@ const LAYER_SIZE: usize = WIDTH * HEIGHT;
 
 fn get_counts(layer: &[u32]) -> (u32, u32, u32) {
     let mut counts = [0, 0, 0];
-    for d in 0..=2 {
-	counts[d] = layer.iter()
+    for (d, count) in counts.iter_mut().enumerate() {
+	*count = layer.iter()
 	    .filter(|x| **x == d as u32)
 	    .count() as u32;
     }
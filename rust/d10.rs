@@ -69,12 +69,12 @@ impl Asteroid {
     
     fn destroy_all(&self, asteroids: &[Asteroid]) -> Vec<(i32, i32)> {
         let mut destroyed_asteroids: Vec<(i32, i32)> = Vec::new();
-	let mut asteroids: Vec<Asteroid> = asteroids.iter().cloned().collect();
+	let mut asteroids: Vec<Asteroid> = asteroids.to_vec();
 
         while asteroids.len() != 1 {
             let mut batch: Vec<Asteroid> = asteroids.iter()
+                .filter(|&a| a != self && self.can_detect(a, &asteroids))
 		.cloned()
-                .filter(|a| a != self && self.can_detect(a, &asteroids))
                 .collect();
 
 	    batch.sort_by(|a, b| {
@@ -94,7 +94,7 @@ impl Asteroid {
 		.collect();
 	    destroyed_asteroids.extend(&locations);
 
-	    asteroids.retain(|a| !batch.contains(&a));
+	    asteroids.retain(|a| !batch.contains(a));
         }
         destroyed_asteroids
     }
@@ -0,0 +1,34 @@
+//! Times d09 part 2 with and without the decoded instruction cache:
+//! `cargo bench -p intcode`.
+
+use std::time::{Duration, Instant};
+
+use intcode::{Cpu, State};
+
+const RUNS: u32 = 10;
+
+fn run(program: &[i128], cache: bool) -> Duration {
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        let mut cpu = Cpu::new(program);
+        cpu.mem.set_code_cache(cache);
+        cpu.add_input(2);
+        assert_eq!(cpu.run(), Ok(State::Halted));
+        assert_eq!(cpu.outputs, vec![50894]);
+    }
+    start.elapsed() / RUNS
+}
+
+fn main() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../d09.in");
+    let program: Vec<i128> = intcode::parse_program(&std::fs::read_to_string(path).unwrap()).unwrap();
+
+    // warm up
+    run(&program, true);
+    let uncached = run(&program, false);
+    let cached = run(&program, true);
+    println!("d09 part 2, mean of {} runs", RUNS);
+    println!("  uncached: {:>8.2?}", uncached);
+    println!("  cached:   {:>8.2?}", cached);
+    println!("  speedup:  {:>8.2}x", uncached.as_secs_f64() / cached.as_secs_f64());
+}
@@ -0,0 +1,314 @@
+//! Ahead-of-time compilation of Intcode programs to Rust.
+//!
+//! `compile` turns a program into the source of a module, usually written
+//! from a build script and pulled in with `include!`:
+//!
+//! ```text
+//! pub const PROGRAM: &[i128] = &[...];
+//! pub fn machine() -> Machine { ... }
+//! ```
+//!
+//! Every canonically encoded instruction of the program becomes native
+//! code. A `Machine` runs that code as long as the words of the instruction
+//! at pc still hold their original values and falls back to the interpreter
+//! for anything else, so self-modifying programs behave exactly as they do
+//! on a `Cpu`.
+
+use std::fmt::Write;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+use crate::cpu::{Cpu, Observer, State};
+use crate::disasm::{self, Item};
+use crate::error::Error;
+use crate::op::{Op, ParamMode};
+
+/// Executes the compiled instruction at `pc`.
+pub type Exec = fn(&mut Context, usize) -> Result<State, Error>;
+
+/// What compiled code sees of the machine. Its helpers fail the same way
+/// the interpreter does.
+pub struct Context<'a> {
+    pub cpu: &'a mut Cpu,
+    dirty: &'a mut [bool],
+}
+
+impl Context<'_> {
+    /// Checks a position-mode address.
+    pub fn addr(&self, addr: i128) -> Result<usize, Error> {
+        self.cpu.address(&addr)
+    }
+
+    /// Resolves a relative-mode address.
+    pub fn rel(&self, offset: i128) -> Result<usize, Error> {
+        self.cpu.address(&self.cpu.relative(&offset)?)
+    }
+
+    pub fn get(&self, addr: usize) -> i128 {
+        self.cpu.mem[addr]
+    }
+
+    pub fn set(&mut self, addr: usize, x: i128) {
+        if let Some(dirty) = self.dirty.get_mut(addr) {
+            *dirty = true;
+        }
+        self.cpu.mem[addr] = x;
+    }
+
+    pub fn add(&self, a: i128, b: i128) -> Result<i128, Error> {
+        self.cpu.add(&a, &b)
+    }
+
+    pub fn mul(&self, a: i128, b: i128) -> Result<i128, Error> {
+        self.cpu.mul(&a, &b)
+    }
+
+    pub fn jump(&mut self, target: i128) -> Result<(), Error> {
+        self.cpu.jump(&target)
+    }
+
+    pub fn adjust_base(&mut self, a: i128) -> Result<(), Error> {
+        self.cpu.adjust_base(&a)
+    }
+}
+
+// marks program words the interpreter overwrites
+struct CodeWrites<'a>(&'a mut [bool]);
+
+impl Observer<i128> for CodeWrites<'_> {
+    fn write(&mut self, _pc: usize, addr: usize, _old: &i128, _new: &i128) {
+        if let Some(dirty) = self.0.get_mut(addr) {
+            *dirty = true;
+        }
+    }
+}
+
+/// A `Cpu` running compiled code where it can. It dereferences to the
+/// underlying `Cpu` for its registers, memory and queues; its own `step`
+/// and `run*` methods are the ones that use the compiled code.
+#[derive(Clone)]
+pub struct Machine {
+    cpu: Cpu,
+    original: Arc<[i128]>,
+    // instruction length of every compiled address, 0 if not compiled
+    lens: &'static [u8],
+    exec: Exec,
+    dirty: Vec<bool>,
+    // memory may have been changed through `DerefMut`
+    resync: bool,
+}
+
+impl Machine {
+    /// Used by generated modules.
+    pub fn new(program: &[i128], lens: &'static [u8], exec: Exec) -> Machine {
+        Machine {
+            cpu: Cpu::new(program),
+            original: program.into(),
+            lens,
+            exec,
+            dirty: vec![false; lens.len()],
+            resync: false,
+        }
+    }
+
+    /// A machine without compiled code, for programs that were not known
+    /// at build time.
+    pub fn interpreted(program: &[i128]) -> Machine {
+        fn exec(_: &mut Context, pc: usize) -> Result<State, Error> {
+            unreachable!("no compiled code at {}", pc)
+        }
+        Machine::new(program, &[], exec)
+    }
+
+    pub fn into_cpu(self) -> Cpu {
+        self.cpu
+    }
+
+    pub fn add_input(&mut self, x: i128) {
+        self.cpu.add_input(x);
+    }
+
+    fn resync(&mut self) {
+        for (addr, dirty) in self.dirty.iter_mut().enumerate() {
+            *dirty = self.cpu.mem[addr] != self.original[addr];
+        }
+        self.resync = false;
+    }
+
+    /// `Cpu::step`, running compiled code when the instruction at pc is
+    /// unmodified.
+    pub fn step(&mut self) -> Result<State, Error> {
+        if self.resync {
+            self.resync();
+        }
+        let pc = self.cpu.pc;
+        let len = self.lens.get(pc).copied().unwrap_or(0) as usize;
+        if len > 0 && pc + len <= self.cpu.mem.limit() && !self.dirty[pc..pc + len].contains(&true) {
+            let mut context = Context { cpu: &mut self.cpu, dirty: &mut self.dirty };
+            return (self.exec)(&mut context, pc);
+        }
+        self.cpu.step_with(&mut CodeWrites(&mut self.dirty))
+    }
+
+    pub fn run(&mut self) -> Result<State, Error> {
+        loop {
+            match self.step()? {
+                State::Running => continue,
+                State::Output(x) => self.cpu.outputs.push(x),
+                state => return Ok(state),
+            }
+        }
+    }
+
+    pub fn run_for(&mut self, max_steps: usize) -> Result<State, Error> {
+        for _ in 0..max_steps {
+            match self.step()? {
+                State::Running => continue,
+                State::Output(x) => self.cpu.outputs.push(x),
+                state => return Ok(state),
+            }
+        }
+        Ok(State::StepLimitReached)
+    }
+
+    pub fn run_until_output(&mut self) -> Result<State, Error> {
+        loop {
+            match self.step()? {
+                State::Running => continue,
+                state => return Ok(state),
+            }
+        }
+    }
+}
+
+impl Deref for Machine {
+    type Target = Cpu;
+
+    fn deref(&self) -> &Cpu {
+        &self.cpu
+    }
+}
+
+impl DerefMut for Machine {
+    fn deref_mut(&mut self) -> &mut Cpu {
+        self.resync = true;
+        &mut self.cpu
+    }
+}
+
+fn operand(mode: ParamMode, param: i128) -> String {
+    match mode {
+        ParamMode::Position => format!("m.get(m.addr({})?)", param),
+        ParamMode::Immediate => format!("{}", param),
+        ParamMode::Relative => format!("m.get(m.rel({})?)", param),
+    }
+}
+
+fn destination(mode: ParamMode, param: i128) -> String {
+    match mode {
+        ParamMode::Relative => format!("m.rel({})?", param),
+        _ => format!("m.addr({})?", param),
+    }
+}
+
+/// The body of the match arm for one instruction, evaluating to its state.
+fn compile_instr(addr: usize, op: Op, modes: &[ParamMode; 3], params: &[i128]) -> String {
+    let next = addr + params.len() + 1;
+    let mut code = String::new();
+    let mut line = |text: String| writeln!(code, "            {}", text).unwrap();
+    match op {
+        Op::Add | Op::Mul | Op::LessThan | Op::Equals => {
+            line(format!("let a = {};", operand(modes[0], params[0])));
+            line(format!("let b = {};", operand(modes[1], params[1])));
+            line(match op {
+                Op::Add => "let x = m.add(a, b)?;".to_string(),
+                Op::Mul => "let x = m.mul(a, b)?;".to_string(),
+                Op::LessThan => "let x = i128::from(a < b);".to_string(),
+                _ => "let x = i128::from(a == b);".to_string(),
+            });
+            line(format!("let addr = {};", destination(modes[2], params[2])));
+            line("m.set(addr, x);".to_string());
+        }
+        Op::Input => {
+            line("let x = match m.cpu.inputs.front() {".to_string());
+            line("    Some(&x) => x,".to_string());
+            line("    None => return Ok(State::NeedsInput),".to_string());
+            line("};".to_string());
+            line(format!("let addr = {};", destination(modes[0], params[0])));
+            line("m.set(addr, x);".to_string());
+            line("m.cpu.inputs.pop_front();".to_string());
+        }
+        Op::Output => {
+            line(format!("let a = {};", operand(modes[0], params[0])));
+            line(format!("m.cpu.pc = {};", next));
+            line("State::Output(a)".to_string());
+            return code;
+        }
+        Op::JmpIfTrue | Op::JmpIfFalse => {
+            line(format!("let a = {};", operand(modes[0], params[0])));
+            line(format!("let b = {};", operand(modes[1], params[1])));
+            line(format!("if a {} 0 {{", if op == Op::JmpIfTrue { "!=" } else { "==" }));
+            line("    m.jump(b)?;".to_string());
+            line("} else {".to_string());
+            line(format!("    m.cpu.pc = {};", next));
+            line("}".to_string());
+            line("State::Running".to_string());
+            return code;
+        }
+        Op::AdjustRelBase => {
+            line(format!("let a = {};", operand(modes[0], params[0])));
+            line("m.adjust_base(a)?;".to_string());
+        }
+        Op::Halt => {
+            line("State::Halted".to_string());
+            return code;
+        }
+    }
+    line(format!("m.cpu.pc = {};", next));
+    line("State::Running".to_string());
+    code
+}
+
+/// Rust source of a module running `program` natively. `source` names the
+/// program in the header comment.
+pub fn compile(program: &[i128], source: &str) -> String {
+    let mut lens = vec![0; program.len()];
+    let mut arms = String::new();
+    for (addr, len) in lens.iter_mut().enumerate() {
+        let (op, modes, params) = match disasm::decode_at(program, addr) {
+            Some(Item::Instr { op, modes, params, .. }) => (op, modes, params),
+            _ => continue,
+        };
+        // writing through an immediate operand is an error; leave it to the
+        // interpreter to report
+        if op.out_param().is_some_and(|n| modes[n] == ParamMode::Immediate) {
+            continue;
+        }
+        *len = params.len() + 1;
+        writeln!(arms, "        {} => {{", addr).unwrap();
+        arms.push_str(&compile_instr(addr, op, &modes, &params));
+        writeln!(arms, "        }}").unwrap();
+    }
+
+    let join = |words: Vec<String>| words.join(", ");
+    let mut out = String::new();
+    writeln!(out, "// Compiled from {} by intcode::aot; do not edit.", source).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "use intcode::aot::{{Context, Machine}};").unwrap();
+    writeln!(out, "use intcode::{{Error, State}};").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "pub const PROGRAM: &[i128] = &[{}];", join(program.iter().map(|x| x.to_string()).collect())).unwrap();
+    writeln!(out, "const LENS: &[u8] = &[{}];", join(lens.iter().map(|x| x.to_string()).collect())).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "pub fn machine() -> Machine {{").unwrap();
+    writeln!(out, "    Machine::new(PROGRAM, LENS, exec)").unwrap();
+    writeln!(out, "}}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "fn exec(m: &mut Context, pc: usize) -> Result<State, Error> {{").unwrap();
+    writeln!(out, "    Ok(match pc {{").unwrap();
+    out.push_str(&arms);
+    writeln!(out, "        _ => unreachable!(\"no compiled code at {{}}\", pc),").unwrap();
+    writeln!(out, "    }})").unwrap();
+    writeln!(out, "}}").unwrap();
+    out
+}
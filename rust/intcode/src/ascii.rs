@@ -0,0 +1,145 @@
+//! ASCII input and output, for programs that talk in text.
+//!
+//! Text goes in as one character code per input, so it has to be ASCII.
+//! Outputs that are ASCII codes come back as text; anything else is passed
+//! through as a number.
+
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use crate::cpu::{Cpu, State};
+use crate::word::Word;
+
+/// A run of output text, or an output that is not an ASCII code.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Chunk<W = i128> {
+    Text(String),
+    Value(W),
+}
+
+fn ascii<W: Word>(x: &W) -> Option<char> {
+    x.to_i128()
+        .and_then(|x| u8::try_from(x).ok())
+        .filter(u8::is_ascii)
+        .map(char::from)
+}
+
+/// A character that has no ASCII code, at byte offset `pos` of the text.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct NotAscii {
+    pub pos: usize,
+    pub c: char,
+}
+
+impl fmt::Display for NotAscii {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} at {} is not an ASCII character", self.c, self.pos)
+    }
+}
+
+impl std::error::Error for NotAscii {}
+
+/// The character codes of `text`, as inputs.
+pub fn encode<W: Word>(text: &str) -> Result<Vec<W>, NotAscii> {
+    match text.char_indices().find(|(_, c)| !c.is_ascii()) {
+        Some((pos, c)) => Err(NotAscii { pos, c }),
+        None => Ok(text.bytes().map(|b| W::from_i64(b.into())).collect()),
+    }
+}
+
+/// Splits outputs into text and other values.
+pub fn decode<W: Word>(outputs: &[W]) -> Vec<Chunk<W>> {
+    let mut chunks = Vec::new();
+    for x in outputs {
+        match (ascii(x), chunks.last_mut()) {
+            (Some(c), Some(Chunk::Text(text))) => text.push(c),
+            (Some(c), _) => chunks.push(Chunk::Text(c.to_string())),
+            (None, _) => chunks.push(Chunk::Value(x.clone())),
+        }
+    }
+    chunks
+}
+
+// values that are not text go on a line of their own
+fn render_value<W: Word>(out: &mut String, x: &W) {
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+    out.push_str(&x.to_string());
+    out.push('\n');
+}
+
+/// Outputs as text, with values that are not ASCII codes written out in
+/// decimal on lines of their own.
+pub fn render<W: Word>(outputs: &[W]) -> String {
+    let mut out = String::new();
+    for x in outputs {
+        match ascii(x) {
+            Some(c) => out.push(c),
+            None => render_value(&mut out, x),
+        }
+    }
+    out
+}
+
+impl<W: Word> Cpu<W> {
+    /// Queues the character codes of `text` as inputs. Nothing is queued
+    /// if it is not all ASCII.
+    pub fn add_text(&mut self, text: &str) -> Result<(), NotAscii> {
+        self.inputs.extend(encode(text)?);
+        Ok(())
+    }
+
+    /// Queues `line` followed by a newline.
+    pub fn add_line(&mut self, line: &str) -> Result<(), NotAscii> {
+        self.add_text(line)?;
+        self.add_input(W::from_i64('\n' as i64));
+        Ok(())
+    }
+
+    /// Takes the outputs so far, rendered as by `render`.
+    pub fn take_text(&mut self) -> String {
+        let text = render(&self.outputs);
+        self.outputs.clear();
+        text
+    }
+}
+
+/// Runs `cpu` as a terminal program: whenever it needs input it gets the
+/// next line of `input`, and its outputs are written to `output` as they
+/// are produced. Returns when the machine halts, or needs input after
+/// `input` has ended. Machine errors and lines that are not ASCII are
+/// returned as `io::Error`s.
+pub fn interact<W: Word, R: BufRead, T: Write>(cpu: &mut Cpu<W>, mut input: R, mut output: T) -> io::Result<State<W>> {
+    let mut line_start = true;
+    loop {
+        match cpu.run_until_output().map_err(io::Error::other)? {
+            State::Output(x) => match ascii(&x) {
+                Some(c) => {
+                    write!(output, "{}", c)?;
+                    line_start = c == '\n';
+                }
+                None => {
+                    if !line_start {
+                        writeln!(output)?;
+                    }
+                    writeln!(output, "{}", x)?;
+                    line_start = true;
+                }
+            },
+            State::NeedsInput => {
+                output.flush()?;
+                let mut line = String::new();
+                if input.read_line(&mut line)? == 0 {
+                    return Ok(State::NeedsInput);
+                }
+                cpu.add_line(line.trim_end_matches(['\n', '\r']))
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            }
+            state => {
+                output.flush()?;
+                return Ok(state);
+            }
+        }
+    }
+}
@@ -0,0 +1,219 @@
+//! Assembler for the listing syntax printed by the disassembler.
+//!
+//! ```text
+//! ; comments run to the end of the line
+//! start:  IN   [n]            ; position operand
+//!         JF   [n], #done     ; immediate operand, labels are addresses
+//!         ARB  #1
+//!         OUT  rb-1           ; relative operand
+//!         JT   #1, #start
+//! done:   HLT
+//! n:      db   0, n+1, $      ; data words; `$` is the current item
+//!         ds   4              ; four zero words
+//! ```
+//!
+//! A number followed by a colon, as in the disassembler's address column,
+//! asserts the address of the item on that line.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::op::{self, Op, ParamMode};
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct AsmError {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.msg)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+enum Body<'a> {
+    Instr(Op, Vec<&'a str>),
+    Data(Vec<&'a str>),
+    Space(&'a str),
+}
+
+struct Line<'a> {
+    number: usize,
+    addr: usize,
+    body: Body<'a>,
+}
+
+fn is_ident(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn split_operands(s: &str) -> Vec<&str> {
+    if s.trim().is_empty() {
+        Vec::new()
+    } else {
+        s.split(',').map(|x| x.trim()).collect()
+    }
+}
+
+struct Assembler {
+    labels: HashMap<String, i128>,
+}
+
+impl Assembler {
+    /// Evaluates sums and differences of numbers, labels and `$`.
+    fn eval(&self, expr: &str, here: usize, line: usize) -> Result<i128, AsmError> {
+        let err = |msg: String| AsmError { line, msg };
+        let expr = expr.trim();
+        if expr.is_empty() {
+            return Err(err("missing expression".to_string()));
+        }
+        let mut total: i128 = 0;
+        let mut sign = 1;
+        let mut rest = expr;
+        loop {
+            rest = rest.trim_start();
+            if let Some(r) = rest.strip_prefix('-') {
+                sign = -sign;
+                rest = r;
+                continue;
+            }
+            if let Some(r) = rest.strip_prefix('+') {
+                rest = r;
+                continue;
+            }
+            let end = rest.find(['+', '-']).unwrap_or(rest.len());
+            let term = rest[..end].trim();
+            let value = if term == "$" {
+                here as i128
+            } else if let Ok(x) = term.parse::<i128>() {
+                x
+            } else if let Some(&x) = self.labels.get(term) {
+                x
+            } else if is_ident(term) {
+                return Err(err(format!("unknown label `{}`", term)));
+            } else {
+                return Err(err(format!("bad expression `{}`", expr)));
+            };
+            total = value.checked_mul(sign)
+                .and_then(|value| total.checked_add(value))
+                .ok_or_else(|| err(format!("`{}` overflows", expr)))?;
+            rest = &rest[end..];
+            if rest.is_empty() {
+                return Ok(total);
+            }
+            sign = 1;
+        }
+    }
+
+    fn operand(&self, text: &str, here: usize, line: usize) -> Result<(ParamMode, i128), AsmError> {
+        if let Some(inner) = text.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+            Ok((ParamMode::Position, self.eval(inner, here, line)?))
+        } else if let Some(expr) = text.strip_prefix('#') {
+            Ok((ParamMode::Immediate, self.eval(expr, here, line)?))
+        } else if let Some(offset) = text.strip_prefix("rb") {
+            let offset = if offset.trim().is_empty() { 0 } else { self.eval(offset, here, line)? };
+            Ok((ParamMode::Relative, offset))
+        } else {
+            Err(AsmError { line, msg: format!("operand `{}` needs a mode: [x], #x or rb+x", text) })
+        }
+    }
+}
+
+fn parse_lines(text: &str) -> Result<(Vec<Line<'_>>, HashMap<String, i128>), AsmError> {
+    let mut lines = Vec::new();
+    let mut labels = HashMap::new();
+    let mut addr = 0;
+    for (i, raw) in text.lines().enumerate() {
+        let number = i + 1;
+        let err = |msg: String| AsmError { line: number, msg };
+        let mut rest = raw.split(';').next().unwrap().trim();
+
+        while let Some((head, tail)) = rest.split_once(':') {
+            let head = head.trim();
+            if let Ok(expected) = head.parse::<usize>() {
+                if expected != addr {
+                    return Err(err(format!("item is at address {}, not {}", addr, expected)));
+                }
+            } else if is_ident(head) {
+                if labels.insert(head.to_string(), addr as i128).is_some() {
+                    return Err(err(format!("label `{}` defined twice", head)));
+                }
+            } else {
+                break;
+            }
+            rest = tail.trim();
+        }
+        if rest.is_empty() {
+            continue;
+        }
+
+        let (name, operands) = match rest.split_once(char::is_whitespace) {
+            Some((name, operands)) => (name, operands.trim()),
+            None => (rest, ""),
+        };
+        let body = if name.eq_ignore_ascii_case("db") {
+            Body::Data(split_operands(operands))
+        } else if name.eq_ignore_ascii_case("ds") {
+            Body::Space(operands)
+        } else if let Some(op) = Op::from_mnemonic(name) {
+            let operands = split_operands(operands);
+            if operands.len() != op.n_params() {
+                return Err(err(format!("{} takes {} operands, got {}",
+                                       op.mnemonic(), op.n_params(), operands.len())));
+            }
+            Body::Instr(op, operands)
+        } else {
+            return Err(err(format!("unknown mnemonic `{}`", name)));
+        };
+
+        let size = match &body {
+            Body::Instr(op, _) => op.n_params() + 1,
+            Body::Data(words) => words.len(),
+            Body::Space(count) => {
+                // sizes must be known before labels are, so only literals
+                count.trim().parse::<usize>()
+                    .map_err(|_| err(format!("ds needs a literal size, got `{}`", count)))?
+            }
+        };
+        lines.push(Line { number, addr, body });
+        addr = addr.checked_add(size).ok_or_else(|| err("program is too large".to_string()))?;
+    }
+    Ok((lines, labels))
+}
+
+/// Assembles source text into a program.
+pub fn assemble(text: &str) -> Result<Vec<i128>, AsmError> {
+    let (lines, labels) = parse_lines(text)?;
+    let asm = Assembler { labels };
+    let mut program = Vec::new();
+    for line in lines {
+        match line.body {
+            Body::Instr(op, operands) => {
+                let mut modes = Vec::new();
+                let mut params = Vec::new();
+                for operand in operands {
+                    let (mode, param) = asm.operand(operand, line.addr, line.number)?;
+                    modes.push(mode);
+                    params.push(param);
+                }
+                program.push(op::pack_instr(op, &modes) as i128);
+                program.extend(params);
+            }
+            Body::Data(words) => {
+                for word in words {
+                    program.push(asm.eval(word, line.addr, line.number)?);
+                }
+            }
+            Body::Space(count) => {
+                let count: usize = count.trim().parse().unwrap();
+                program.resize(program.len() + count, 0);
+            }
+        }
+    }
+    Ok(program)
+}
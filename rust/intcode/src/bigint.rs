@@ -0,0 +1,232 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Mul, Neg};
+use std::str::FromStr;
+
+const BASE: u64 = 1 << 32;
+const DECIMAL_CHUNK: u64 = 1_000_000_000;
+
+/// Arbitrary-precision signed integer, just big enough for Intcode:
+/// addition, multiplication, comparison and decimal conversion.
+#[derive(Clone, Default, PartialEq, Eq, Hash, Debug)]
+pub struct BigInt {
+    negative: bool,
+    // little-endian base 2^32 digits without trailing zeros; empty for 0
+    mag: Vec<u32>,
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct ParseBigIntError;
+
+impl fmt::Display for ParseBigIntError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid integer literal")
+    }
+}
+
+impl std::error::Error for ParseBigIntError {}
+
+fn trim(mag: &mut Vec<u32>) {
+    while mag.last() == Some(&0) {
+        mag.pop();
+    }
+}
+
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut res = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0;
+    for i in 0..a.len().max(b.len()) {
+        let sum = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        res.push(sum as u32);
+        carry = sum >> 32;
+    }
+    if carry > 0 {
+        res.push(carry as u32);
+    }
+    res
+}
+
+// requires a >= b
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut res = Vec::with_capacity(a.len());
+    let mut borrow = 0;
+    for (i, &x) in a.iter().enumerate() {
+        let y = *b.get(i).unwrap_or(&0) as u64 + borrow;
+        if (x as u64) >= y {
+            res.push((x as u64 - y) as u32);
+            borrow = 0;
+        } else {
+            res.push((BASE + x as u64 - y) as u32);
+            borrow = 1;
+        }
+    }
+    trim(&mut res);
+    res
+}
+
+fn mul_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut res = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0;
+        for (j, &y) in b.iter().enumerate() {
+            let cur = res[i + j] as u64 + x as u64 * y as u64 + carry;
+            res[i + j] = cur as u32;
+            carry = cur >> 32;
+        }
+        res[i + b.len()] = carry as u32;
+    }
+    trim(&mut res);
+    res
+}
+
+// divides in place and returns the remainder
+fn div_small(mag: &mut Vec<u32>, d: u64) -> u64 {
+    let mut rem = 0;
+    for digit in mag.iter_mut().rev() {
+        let cur = (rem << 32) | *digit as u64;
+        *digit = (cur / d) as u32;
+        rem = cur % d;
+    }
+    trim(mag);
+    rem
+}
+
+impl BigInt {
+    fn from_parts(negative: bool, mut mag: Vec<u32>) -> BigInt {
+        trim(&mut mag);
+        BigInt { negative: negative && !mag.is_empty(), mag }
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn to_i128(&self) -> Option<i128> {
+        if self.mag.len() > 4 {
+            return None;
+        }
+        let abs = self.mag.iter().rev().fold(0u128, |acc, &d| (acc << 32) | d as u128);
+        if self.negative {
+            if abs <= i128::MAX as u128 + 1 {
+                Some((abs as i128).wrapping_neg())
+            } else {
+                None
+            }
+        } else {
+            i128::try_from(abs).ok()
+        }
+    }
+}
+
+impl From<i128> for BigInt {
+    fn from(x: i128) -> BigInt {
+        let mut abs = x.unsigned_abs();
+        let mut mag = Vec::new();
+        while abs > 0 {
+            mag.push(abs as u32);
+            abs >>= 32;
+        }
+        BigInt::from_parts(x < 0, mag)
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_mag(&self.mag, &other.mag),
+            (true, true) => cmp_mag(&other.mag, &self.mag),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Add for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::from_parts(self.negative, add_mag(&self.mag, &other.mag));
+        }
+        match cmp_mag(&self.mag, &other.mag) {
+            Ordering::Less => BigInt::from_parts(other.negative, sub_mag(&other.mag, &self.mag)),
+            _ => BigInt::from_parts(self.negative, sub_mag(&self.mag, &other.mag)),
+        }
+    }
+}
+
+impl Mul for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: &BigInt) -> BigInt {
+        BigInt::from_parts(self.negative != other.negative, mul_mag(&self.mag, &other.mag))
+    }
+}
+
+impl Neg for &BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::from_parts(!self.negative, self.mag.clone())
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.mag.is_empty() {
+            return write!(f, "0");
+        }
+        let mut mag = self.mag.clone();
+        let mut chunks = Vec::new();
+        while !mag.is_empty() {
+            chunks.push(div_small(&mut mag, DECIMAL_CHUNK));
+        }
+        let mut s = String::new();
+        if self.negative {
+            s.push('-');
+        }
+        s.push_str(&chunks.pop().unwrap().to_string());
+        for chunk in chunks.iter().rev() {
+            s.push_str(&format!("{:09}", chunk));
+        }
+        f.pad(&s)
+    }
+}
+
+impl FromStr for BigInt {
+    type Err = ParseBigIntError;
+
+    fn from_str(s: &str) -> Result<BigInt, ParseBigIntError> {
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        if digits.is_empty() || !digits.bytes().all(|c| c.is_ascii_digit()) {
+            return Err(ParseBigIntError);
+        }
+        let mut mag: Vec<u32> = Vec::new();
+        for c in digits.bytes() {
+            let mut carry = (c - b'0') as u64;
+            for digit in mag.iter_mut() {
+                let cur = *digit as u64 * 10 + carry;
+                *digit = cur as u32;
+                carry = cur >> 32;
+            }
+            if carry > 0 {
+                mag.push(carry as u32);
+            }
+        }
+        Ok(BigInt::from_parts(negative, mag))
+    }
+}
@@ -0,0 +1,27 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::process;
+
+use intcode::{ascii, Cpu, State};
+
+fn main() -> io::Result<()> {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: ascii <program>");
+            process::exit(1);
+        }
+    };
+    let text = fs::read_to_string(path)?;
+    let program: Vec<i128> = intcode::parse_program(&text)
+        .expect("failed to parse number");
+
+    let mut cpu = Cpu::new(&program);
+    let stdin = io::stdin();
+    if ascii::interact(&mut cpu, stdin.lock(), io::stdout())? == State::NeedsInput {
+        eprintln!("end of input, machine still waiting at pc {}", cpu.pc);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,24 @@
+use std::env;
+use std::fs;
+use std::process;
+
+fn main() -> std::io::Result<()> {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: asm <source>");
+            process::exit(1);
+        }
+    };
+    let text = fs::read_to_string(path)?;
+
+    match intcode::asm::assemble(&text) {
+        Ok(program) => println!("{}", intcode::format_program(&program)),
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,22 @@
+use std::env;
+use std::fs;
+use std::process;
+
+use intcode::cfg::Cfg;
+
+fn main() -> std::io::Result<()> {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: cfg <program>");
+            process::exit(1);
+        }
+    };
+    let text = fs::read_to_string(path)?;
+    let program: Vec<i128> = intcode::parse_program(&text)
+        .expect("failed to parse number");
+
+    print!("{}", Cfg::build(&program).to_dot());
+
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::process;
+
+use intcode::debugger::Debugger;
+use intcode::Cpu;
+
+fn main() -> io::Result<()> {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: debugger <program>");
+            process::exit(1);
+        }
+    };
+    let text = fs::read_to_string(path)?;
+    let program: Vec<i128> = intcode::parse_program(&text)
+        .expect("failed to parse number");
+
+    let mut dbg = Debugger::new(Cpu::new(&program));
+    println!("{}", dbg.current());
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("(idb) ");
+        io::stdout().flush()?;
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => break,
+        };
+        if matches!(line.trim(), "q" | "quit") {
+            break;
+        }
+        match dbg.command(&line) {
+            Ok(out) if out.is_empty() => (),
+            Ok(out) => println!("{}", out),
+            Err(err) => println!("{}", err),
+        }
+    }
+
+    Ok(())
+}
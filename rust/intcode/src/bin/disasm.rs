@@ -0,0 +1,20 @@
+use std::env;
+use std::fs;
+use std::process;
+
+fn main() -> std::io::Result<()> {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: disasm <program>");
+            process::exit(1);
+        }
+    };
+    let text = fs::read_to_string(path)?;
+    let program: Vec<i128> = intcode::parse_program(&text)
+        .expect("failed to parse number");
+
+    print!("{}", intcode::disasm::listing(&program));
+
+    Ok(())
+}
@@ -0,0 +1,25 @@
+use std::env;
+use std::process;
+
+use intcode::fuzz::Fuzzer;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let seed = args.get(1).and_then(|seed| seed.parse().ok());
+    let cases = args.get(2).map_or(Some(10_000), |cases| cases.parse().ok());
+    let (seed, cases) = match (seed, cases) {
+        (Some(seed), Some(cases)) => (seed, cases),
+        _ => {
+            eprintln!("usage: fuzz <seed> [cases]");
+            process::exit(1);
+        }
+    };
+
+    match Fuzzer::new(seed).run(cases) {
+        None => println!("backends agree on {} cases", cases),
+        Some(mismatch) => {
+            print!("{}", mismatch);
+            process::exit(2);
+        }
+    }
+}
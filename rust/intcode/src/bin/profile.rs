@@ -0,0 +1,33 @@
+use std::env;
+use std::fs;
+use std::process;
+
+use intcode::profile::Profiler;
+use intcode::{Cpu, State};
+
+fn main() -> std::io::Result<()> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        eprintln!("usage: profile <program> [input...]");
+        process::exit(1);
+    }
+    let text = fs::read_to_string(&args[1])?;
+    let program: Vec<i128> = intcode::parse_program(&text)
+        .expect("failed to parse number");
+
+    let mut cpu = Cpu::new(&program);
+    for arg in &args[2..] {
+        cpu.add_input(arg.parse().expect("failed to parse input"));
+    }
+    let mut profiler = Profiler::new();
+    match cpu.run_with(&mut profiler) {
+        Ok(State::Halted) => (),
+        Ok(state) => eprintln!("stopped: {:?}", state),
+        Err(err) => eprintln!("error: {}", err),
+    }
+    let outputs: Vec<String> = cpu.outputs.iter().map(|x| x.to_string()).collect();
+    println!("outputs: {}\n", outputs.join(","));
+    print!("{}", profiler.report(&program));
+
+    Ok(())
+}
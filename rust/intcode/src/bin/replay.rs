@@ -0,0 +1,28 @@
+use std::env;
+use std::fs;
+use std::io::BufReader;
+use std::process;
+
+use intcode::trace::{self, Tracer};
+
+fn main() -> std::io::Result<()> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        eprintln!("usage: replay <program> <trace>");
+        process::exit(1);
+    }
+    let text = fs::read_to_string(&args[1])?;
+    let program: Vec<i128> = intcode::parse_program(&text)
+        .expect("failed to parse number");
+    let tracer = Tracer::load(BufReader::new(fs::File::open(&args[2])?))?;
+
+    match trace::replay(&program, &tracer.steps) {
+        Ok(n) => println!("replayed {} steps, no divergence", n),
+        Err(divergence) => {
+            println!("diverged at {}", divergence);
+            process::exit(2);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,140 @@
+//! Control-flow graphs of the statically reachable code of a program.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use crate::disasm::{self, Item};
+use crate::op::{Op, ParamMode};
+use crate::word::Word;
+
+/// A run of instructions that is only entered at its first instruction and
+/// only left after its last one.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Block<W = i128> {
+    pub start: usize,
+    pub instrs: Vec<Item<W>>,
+    /// Start addresses of the blocks control can pass to.
+    pub succs: Vec<usize>,
+    /// The block ends in a jump through memory, so it may also continue at
+    /// addresses the graph does not know.
+    pub unresolved: bool,
+}
+
+impl<W: Word> Block<W> {
+    /// One past the last address of the block.
+    pub fn end(&self) -> usize {
+        self.instrs.last().map_or(self.start, |item| item.addr() + item.len())
+    }
+
+    fn last(&self) -> &Item<W> {
+        self.instrs.last().unwrap()
+    }
+}
+
+fn is_jump(op: Op) -> bool {
+    matches!(op, Op::JmpIfTrue | Op::JmpIfFalse | Op::Halt)
+}
+
+/// Whether the instruction jumps to a target computed at run time.
+fn is_indirect<W: Word>(item: &Item<W>) -> bool {
+    match item {
+        Item::Instr { op: op @ (Op::JmpIfTrue | Op::JmpIfFalse), modes, params, .. } => {
+            let never_taken = modes[0] == ParamMode::Immediate
+                && params[0].is_zero() != (*op == Op::JmpIfFalse);
+            modes[1] != ParamMode::Immediate && !never_taken
+        }
+        _ => false,
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Cfg<W = i128> {
+    /// Blocks in address order.
+    pub blocks: Vec<Block<W>>,
+}
+
+impl<W: Word> Cfg<W> {
+    /// Splits the code reachable from address 0 into basic blocks, at the
+    /// targets of immediate jumps and after every jump or halt.
+    pub fn build(program: &[W]) -> Cfg<W> {
+        let items: Vec<Item<W>> = disasm::disassemble(program).into_iter()
+            .filter(|item| matches!(item, Item::Instr { .. }))
+            .collect();
+        let mut leaders = BTreeSet::from([0]);
+        for item in &items {
+            if let Item::Instr { op, .. } = item {
+                if is_jump(*op) {
+                    leaders.extend(disasm::successors(item));
+                    leaders.insert(item.addr() + item.len());
+                }
+            }
+        }
+
+        let mut blocks: Vec<Block<W>> = Vec::new();
+        for item in items {
+            let continues = match blocks.last() {
+                Some(block) => {
+                    let last = block.last();
+                    let jumped = matches!(last, Item::Instr { op, .. } if is_jump(*op));
+                    !jumped && block.end() == item.addr() && !leaders.contains(&item.addr())
+                }
+                None => false,
+            };
+            if continues {
+                blocks.last_mut().unwrap().instrs.push(item);
+            } else {
+                blocks.push(Block { start: item.addr(), instrs: vec![item], succs: Vec::new(), unresolved: false });
+            }
+        }
+
+        let starts: BTreeSet<usize> = blocks.iter().map(|block| block.start).collect();
+        for block in &mut blocks {
+            let last = block.last();
+            let mut succs: Vec<usize> = disasm::successors(last).into_iter()
+                .filter(|succ| starts.contains(succ))
+                .collect();
+            succs.dedup();
+            block.unresolved = is_indirect(last);
+            block.succs = succs;
+        }
+        Cfg { blocks }
+    }
+
+    /// The block containing `addr`, if it is code.
+    pub fn block_at(&self, addr: usize) -> Option<&Block<W>> {
+        let i = self.blocks.partition_point(|block| block.start <= addr);
+        self.blocks[..i].last().filter(|block| addr < block.end())
+    }
+
+    /// Graphviz source for the graph. Taken jumps are labelled, and blocks
+    /// ending in an indirect jump point at a shared `indirect` node.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "digraph intcode {{").unwrap();
+        writeln!(out, "    node [shape=box, fontname=\"monospace\"];").unwrap();
+        for block in &self.blocks {
+            let label: String = block.instrs.iter()
+                .map(|item| format!("{}\\l", item).replace('"', "\\\""))
+                .collect();
+            writeln!(out, "    b{} [label=\"{}\"];", block.start, label).unwrap();
+        }
+        if self.blocks.iter().any(|block| block.unresolved) {
+            writeln!(out, "    indirect [shape=ellipse, style=dashed];").unwrap();
+        }
+        for block in &self.blocks {
+            for succ in &block.succs {
+                if *succ == block.end() {
+                    writeln!(out, "    b{} -> b{};", block.start, succ).unwrap();
+                } else {
+                    writeln!(out, "    b{} -> b{} [label=\"{}\"];",
+                             block.start, succ, block.last().text().split(' ').next().unwrap()).unwrap();
+                }
+            }
+            if block.unresolved {
+                writeln!(out, "    b{} -> indirect [style=dashed];", block.start).unwrap();
+            }
+        }
+        writeln!(out, "}}").unwrap();
+        out
+    }
+}
@@ -0,0 +1,379 @@
+use std::collections::VecDeque;
+
+use crate::error::Error;
+use crate::memory::Memory;
+use crate::op::{self, DecodeError, Decoded, Op, ParamMode};
+use crate::word::{Overflow, Word};
+
+/// What the machine did on its last step, or why it stopped running.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum State<W = i128> {
+    Running,
+    Output(W),
+    NeedsInput,
+    Halted,
+    StepLimitReached,
+}
+
+// raw parameter words of an instruction, or why they could not be read
+type Params<W> = [Result<W, Error<W>>; 3];
+
+/// Hooks into the execution of `Cpu::step_with`. Every hook does nothing
+/// by default; `()` is the observer that ignores everything.
+pub trait Observer<W> {
+    /// Called before the instruction at `pc` executes.
+    fn exec(&mut self, _pc: usize, _op: Op) {}
+
+    /// Called when an instruction loads an operand from memory.
+    fn read(&mut self, _pc: usize, _addr: usize, _value: &W) {}
+
+    /// Called after an instruction stores a result into memory.
+    fn write(&mut self, _pc: usize, _addr: usize, _old: &W, _new: &W) {}
+}
+
+impl<W> Observer<W> for () {}
+
+impl<W, O: Observer<W> + ?Sized> Observer<W> for &mut O {
+    fn exec(&mut self, pc: usize, op: Op) {
+        (**self).exec(pc, op)
+    }
+
+    fn read(&mut self, pc: usize, addr: usize, value: &W) {
+        (**self).read(pc, addr, value)
+    }
+
+    fn write(&mut self, pc: usize, addr: usize, old: &W, new: &W) {
+        (**self).write(pc, addr, old, new)
+    }
+}
+
+/// Runs both observers, the first one first.
+impl<W, A: Observer<W>, B: Observer<W>> Observer<W> for (A, B) {
+    fn exec(&mut self, pc: usize, op: Op) {
+        self.0.exec(pc, op);
+        self.1.exec(pc, op);
+    }
+
+    fn read(&mut self, pc: usize, addr: usize, value: &W) {
+        self.0.read(pc, addr, value);
+        self.1.read(pc, addr, value);
+    }
+
+    fn write(&mut self, pc: usize, addr: usize, old: &W, new: &W) {
+        self.0.write(pc, addr, old, new);
+        self.1.write(pc, addr, old, new);
+    }
+}
+
+/// An Intcode machine. Clones share memory pages until they are written,
+/// so forking a machine mid-run is cheap.
+#[derive(Clone)]
+pub struct Cpu<W = i128> {
+    pub pc: usize,
+    pub base_offset: i64,
+    pub mem: Memory<W>,
+    pub outputs: Vec<W>,
+    pub inputs: VecDeque<W>,
+    overflow: Overflow,
+}
+
+impl<W: Word> Cpu<W> {
+    /// Creates a machine that reports arithmetic overflow as an error.
+    pub fn new(program: &[W]) -> Cpu<W> {
+        Cpu::with_overflow(program, Overflow::Check)
+    }
+
+    pub fn with_overflow(program: &[W], overflow: Overflow) -> Cpu<W> {
+        Cpu {
+            pc: 0,
+            base_offset: 0,
+            mem: Memory::new(program),
+            outputs: Vec::new(),
+            inputs: VecDeque::new(),
+            overflow,
+        }
+    }
+
+    pub fn overflow(&self) -> Overflow {
+        self.overflow
+    }
+
+    /// Copies the whole machine state; see `restore`.
+    pub fn snapshot(&self) -> Cpu<W> {
+        self.clone()
+    }
+
+    /// Puts the machine back into a state taken with `snapshot`.
+    pub fn restore(&mut self, snapshot: &Cpu<W>) {
+        self.clone_from(snapshot);
+    }
+
+    pub fn unpack_instr(&self) -> Result<([ParamMode; 3], Op), Error<W>> {
+        if self.pc >= self.mem.limit() {
+            return Err(Error::PcOutOfRange { pc: self.pc });
+        }
+        let instr = &self.mem[self.pc];
+        op::unpack_instr(instr).map_err(|err| match err {
+            DecodeError::UnknownOpcode => Error::UnknownOpcode { pc: self.pc, instr: instr.clone() },
+            DecodeError::UnknownParamMode => Error::UnknownParamMode { pc: self.pc, instr: instr.clone() },
+        })
+    }
+
+    /// Raw word of the instruction at pc, used to give errors context.
+    fn instr(&self) -> W {
+        if self.pc < self.mem.limit() {
+            self.mem[self.pc].clone()
+        } else {
+            W::default()
+        }
+    }
+
+    pub(crate) fn address(&self, addr: &W) -> Result<usize, Error<W>> {
+        let negative = || Error::NegativeAddress { pc: self.pc, instr: self.instr(), addr: addr.clone() };
+        let out_of_range = || Error::AddressOutOfRange { pc: self.pc, instr: self.instr(), addr: addr.clone() };
+        match addr.to_i128() {
+            Some(x) if x < 0 => Err(negative()),
+            Some(x) if x < self.mem.limit() as i128 => Ok(x as usize),
+            Some(_) => Err(out_of_range()),
+            None if *addr < W::default() => Err(negative()),
+            None => Err(out_of_range()),
+        }
+    }
+
+    pub(crate) fn relative(&self, offset: &W) -> Result<W, Error<W>> {
+        W::from_i64(self.base_offset).checked_add(offset)
+            .ok_or_else(|| Error::Overflow { pc: self.pc, instr: self.instr() })
+    }
+
+    fn load<O: Observer<W>>(&self, addr: &W, obs: &mut O) -> Result<W, Error<W>> {
+        let addr = self.address(addr)?;
+        obs.read(self.pc, addr, &self.mem[addr]);
+        Ok(self.mem[addr].clone())
+    }
+
+    /// Raw word of the n-th parameter of the current instruction.
+    fn param(&self, n: usize) -> Result<W, Error<W>> {
+        let addr = self.pc.checked_add(n).filter(|&addr| addr < self.mem.limit())
+            .ok_or(Error::PcOutOfRange { pc: self.pc })?;
+        Ok(self.mem[addr].clone())
+    }
+
+    fn get_value<O: Observer<W>>(&self, val: W, mode: &ParamMode, obs: &mut O) -> Result<W, Error<W>> {
+        match mode {
+            ParamMode::Position => self.load(&val, obs),
+            ParamMode::Immediate => Ok(val),
+            ParamMode::Relative => self.load(&self.relative(&val)?, obs),
+        }
+    }
+
+    fn get_values<O: Observer<W>>(&self, modes: &[ParamMode; 3], params: &Params<W>, obs: &mut O) -> Result<(W, W), Error<W>> {
+        let a = self.get_value(params[0].clone()?, &modes[0], obs)?;
+        let b = self.get_value(params[1].clone()?, &modes[1], obs)?;
+        Ok((a, b))
+    }
+
+    /// Decodes the instruction at pc, from the cache if it was decoded
+    /// before. Parameters past the end of memory are errors, reported only
+    /// if the instruction gets to use them.
+    fn decode(&mut self) -> Result<(Op, [ParamMode; 3], Params<W>), Error<W>> {
+        if let Some(decoded) = self.mem.decoded(self.pc) {
+            return Ok((decoded.op, decoded.modes, decoded.params.clone().map(Ok)));
+        }
+        let (modes, op) = self.unpack_instr()?;
+        let params = [1, 2, 3].map(|n| if n <= op.n_params() { self.param(n) } else { Ok(W::default()) });
+        if params.iter().all(|param| param.is_ok()) {
+            let decoded = Decoded { op, modes, params: params.clone().map(|param| param.unwrap()) };
+            self.mem.cache_decoded(self.pc, decoded);
+        }
+        Ok((op, modes, params))
+    }
+
+    fn set_value<O: Observer<W>>(&mut self, val: W, pos: W, mode: &ParamMode, obs: &mut O) -> Result<(), Error<W>> {
+        let location = match mode {
+            ParamMode::Position => self.address(&pos)?,
+            ParamMode::Immediate => {
+                return Err(Error::ImmediateWrite { pc: self.pc, instr: self.instr() })
+            }
+            ParamMode::Relative => self.address(&self.relative(&pos)?)?,
+        };
+        let old = std::mem::replace(&mut self.mem[location], val);
+        obs.write(self.pc, location, &old, &self.mem[location]);
+        Ok(())
+    }
+
+    pub(crate) fn jump(&mut self, target: &W) -> Result<(), Error<W>> {
+        match target.to_i128() {
+            Some(x) if x < 0 => {
+                Err(Error::NegativeAddress { pc: self.pc, instr: self.instr(), addr: target.clone() })
+            }
+            Some(x) if x <= usize::MAX as i128 => {
+                self.pc = x as usize;
+                Ok(())
+            }
+            _ => Err(Error::AddressOutOfRange { pc: self.pc, instr: self.instr(), addr: target.clone() }),
+        }
+    }
+
+    pub(crate) fn adjust_base(&mut self, a: &W) -> Result<(), Error<W>> {
+        self.base_offset = a.to_i128()
+            .and_then(|a| i64::try_from(a).ok())
+            .and_then(|a| self.base_offset.checked_add(a))
+            .ok_or_else(|| Error::Overflow { pc: self.pc, instr: self.instr() })?;
+        Ok(())
+    }
+
+    pub(crate) fn add(&self, a: &W, b: &W) -> Result<W, Error<W>> {
+        match self.overflow {
+            Overflow::Wrap => Ok(a.wrapping_add(b)),
+            Overflow::Check => a.checked_add(b)
+                .ok_or_else(|| Error::Overflow { pc: self.pc, instr: self.instr() }),
+        }
+    }
+
+    pub(crate) fn mul(&self, a: &W, b: &W) -> Result<W, Error<W>> {
+        match self.overflow {
+            Overflow::Wrap => Ok(a.wrapping_mul(b)),
+            Overflow::Check => a.checked_mul(b)
+                .ok_or_else(|| Error::Overflow { pc: self.pc, instr: self.instr() }),
+        }
+    }
+
+    fn flag(cond: bool) -> W {
+        W::from_i64(if cond { 1 } else { 0 })
+    }
+
+    pub fn add_input(&mut self, x: W) {
+        self.inputs.push_back(x);
+    }
+
+    pub fn transfer_outputs(&mut self, inputs: &[W]) {
+        for x in inputs.iter().rev() {
+            self.add_input(x.clone());
+        }
+    }
+
+    /// Limits the machine to `mem_size` words of memory, but never to fewer
+    /// than were already loaded or written. Memory is still only allocated
+    /// as the program touches it.
+    pub fn set_mem_size(&mut self, mem_size: usize) {
+        self.mem.set_limit(mem_size.max(self.mem.extent()));
+    }
+
+    pub fn step(&mut self) -> Result<State<W>, Error<W>> {
+        self.step_with(&mut ())
+    }
+
+    /// Executes one instruction, reporting its memory traffic to `obs`.
+    pub fn step_with<O: Observer<W>>(&mut self, obs: &mut O) -> Result<State<W>, Error<W>> {
+        let (op, modes, params) = self.decode()?;
+        if op == Op::Input && self.inputs.is_empty() {
+            return Ok(State::NeedsInput);
+        }
+        obs.exec(self.pc, op);
+        match op {
+            Op::Add => {
+                let (a, b) = self.get_values(&modes, &params, obs)?;
+                let res = self.add(&a, &b)?;
+                self.set_value(res, params[2].clone()?, &modes[2], obs)?;
+                self.pc += 4;
+                Ok(State::Running)
+            }
+            Op::Mul => {
+                let (a, b) = self.get_values(&modes, &params, obs)?;
+                let res = self.mul(&a, &b)?;
+                self.set_value(res, params[2].clone()?, &modes[2], obs)?;
+                self.pc += 4;
+                Ok(State::Running)
+            }
+            Op::Input => {
+                let x = self.inputs.front().cloned().unwrap();
+                self.set_value(x, params[0].clone()?, &modes[0], obs)?;
+                self.inputs.pop_front();
+                self.pc += 2;
+                Ok(State::Running)
+            }
+            Op::Output => {
+                let a = self.get_value(params[0].clone()?, &modes[0], obs)?;
+                self.pc += 2;
+                Ok(State::Output(a))
+            }
+            Op::JmpIfTrue => {
+                let (a, b) = self.get_values(&modes, &params, obs)?;
+                if !a.is_zero() {
+                    self.jump(&b)?;
+                } else {
+                    self.pc += 3;
+                }
+                Ok(State::Running)
+            }
+            Op::JmpIfFalse => {
+                let (a, b) = self.get_values(&modes, &params, obs)?;
+                if a.is_zero() {
+                    self.jump(&b)?;
+                } else {
+                    self.pc += 3;
+                }
+                Ok(State::Running)
+            }
+            Op::LessThan => {
+                let (a, b) = self.get_values(&modes, &params, obs)?;
+                self.set_value(Self::flag(a < b), params[2].clone()?, &modes[2], obs)?;
+                self.pc += 4;
+                Ok(State::Running)
+            }
+            Op::Equals => {
+                let (a, b) = self.get_values(&modes, &params, obs)?;
+                self.set_value(Self::flag(a == b), params[2].clone()?, &modes[2], obs)?;
+                self.pc += 4;
+                Ok(State::Running)
+            }
+            Op::AdjustRelBase => {
+                let a = self.get_value(params[0].clone()?, &modes[0], obs)?;
+                self.adjust_base(&a)?;
+                self.pc += 2;
+                Ok(State::Running)
+            }
+            Op::Halt => Ok(State::Halted),
+        }
+    }
+
+    /// Runs until the program halts or waits for input, collecting its
+    /// output into `outputs`.
+    pub fn run(&mut self) -> Result<State<W>, Error<W>> {
+        self.run_with(&mut ())
+    }
+
+    /// `run`, reporting every step to an observer such as a profiler.
+    pub fn run_with<O: Observer<W>>(&mut self, obs: &mut O) -> Result<State<W>, Error<W>> {
+        loop {
+            match self.step_with(obs)? {
+                State::Running => continue,
+                State::Output(x) => self.outputs.push(x),
+                state => return Ok(state),
+            }
+        }
+    }
+
+    /// Like `run`, but gives up after executing `max_steps` instructions.
+    pub fn run_for(&mut self, max_steps: usize) -> Result<State<W>, Error<W>> {
+        for _ in 0..max_steps {
+            match self.step()? {
+                State::Running => continue,
+                State::Output(x) => self.outputs.push(x),
+                state => return Ok(state),
+            }
+        }
+        Ok(State::StepLimitReached)
+    }
+
+    /// Runs until the next output and returns it as `State::Output` without
+    /// buffering it, or returns the state the machine stopped in instead.
+    pub fn run_until_output(&mut self) -> Result<State<W>, Error<W>> {
+        loop {
+            match self.step()? {
+                State::Running => continue,
+                state => return Ok(state),
+            }
+        }
+    }
+}
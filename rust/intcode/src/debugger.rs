@@ -0,0 +1,376 @@
+//! Command interpreter for stepping through a program interactively. The
+//! `debugger` binary feeds it lines from stdin; everything it prints is
+//! returned as a string so it can be driven from tests as well.
+
+use std::collections::BTreeSet;
+
+use crate::cpu::{Cpu, State};
+use crate::disasm::{self, Item};
+use crate::memory::Memory;
+use crate::op::Op;
+use crate::undo::History;
+use crate::watch::{Event, Watcher};
+use crate::word::Word;
+
+// words `x` and items `l` show at most
+const MAX_SHOWN: usize = 1000;
+
+const HELP: &str = "\
+s, step [n]          execute n instructions (default 1)
+c, continue          run until a breakpoint, halt, input wait or error
+rs, rstep [n]        undo n instructions (default 1)
+rc, rcontinue        run backwards to a breakpoint, a watched write or the
+                     oldest remembered step
+b, break [addr|op]   break at an address or on an opcode (e.g. `b OUT`);
+                     without an argument, list breakpoints
+d, delete addr|op    remove a breakpoint
+x addr [n]           show n words of memory (default 8, at most 1000)
+set addr value       write a word of memory
+pc [addr]            show or set the program counter
+rb [value]           show or set the relative base
+in value...          queue input values
+out                  show the outputs buffer
+clear                empty the outputs buffer
+w, watch a[..b] [r|w|rw]  stop on reads and/or writes of an address range
+                     (default w); without an argument, list watchpoints
+unwatch addr         remove the watchpoints covering an address
+smc on|off           stop on writes to already executed instructions
+l, list [addr] [n]   disassemble n items from addr (default pc, 8, at most
+                     1000)
+i, info              show machine registers and queues
+q, quit              leave the debugger";
+
+/// Decodes the instruction stored at `addr` in live memory.
+pub fn decode_mem<W: Word>(mem: &Memory<W>, addr: usize) -> Item<W> {
+    let window = mem.read(addr, 4);
+    match disasm::decode_at(&window, 0) {
+        Some(Item::Instr { op, modes, params, .. }) => Item::Instr { addr, op, modes, params },
+        _ => Item::Data { addr, words: vec![mem[addr].clone()] },
+    }
+}
+
+pub struct Debugger<W = i128> {
+    pub cpu: Cpu<W>,
+    pub breakpoints: BTreeSet<usize>,
+    pub op_breakpoints: Vec<Op>,
+    pub watcher: Watcher<W>,
+    /// Executed steps, for running backwards. Editing registers or memory
+    /// by hand forgets it.
+    pub history: History<W>,
+}
+
+enum Stop<W> {
+    Breakpoint,
+    Watchpoint,
+    Steps,
+    Oldest,
+    State(State<W>),
+    Error(String),
+}
+
+impl<W: Word> Debugger<W> {
+    pub fn new(cpu: Cpu<W>) -> Debugger<W> {
+        Debugger {
+            cpu,
+            breakpoints: BTreeSet::new(),
+            op_breakpoints: Vec::new(),
+            watcher: Watcher::new(),
+            history: History::new(),
+        }
+    }
+
+    pub fn current(&self) -> String {
+        decode_mem(&self.cpu.mem, self.cpu.pc).to_string()
+    }
+
+    fn at_breakpoint(&self) -> bool {
+        if self.breakpoints.contains(&self.cpu.pc) {
+            return true;
+        }
+        match self.cpu.unpack_instr() {
+            Ok((_, op)) => self.op_breakpoints.contains(&op),
+            Err(_) => false,
+        }
+    }
+
+    /// Executes up to `max_steps` instructions, or until a breakpoint or
+    /// watchpoint is hit when `max_steps` is `None`. Outputs are collected
+    /// into the outputs buffer and echoed into `log`, as are watch events.
+    fn execute(&mut self, max_steps: Option<usize>, log: &mut String) -> Stop<W> {
+        let mut steps = 0;
+        loop {
+            if let Some(max) = max_steps {
+                if steps == max {
+                    return Stop::Steps;
+                }
+            } else if steps > 0 && self.at_breakpoint() {
+                return Stop::Breakpoint;
+            }
+            let result = self.history.step(&mut self.cpu, &mut self.watcher);
+            let watched = !self.watcher.events.is_empty();
+            for event in self.watcher.events.drain(..) {
+                log.push_str(&format!("{}\n", event));
+            }
+            match result {
+                Ok(State::Running) => (),
+                Ok(State::Output(x)) => {
+                    log.push_str(&format!("output: {}\n", x));
+                    self.cpu.outputs.push(x);
+                }
+                Ok(state) => return Stop::State(state),
+                Err(err) => return Stop::Error(err.to_string()),
+            }
+            steps += 1;
+            if watched && max_steps.is_none() {
+                return Stop::Watchpoint;
+            }
+        }
+    }
+
+    /// Undoes up to `max_steps` instructions, or until a breakpoint or a
+    /// watched write is undone when `max_steps` is `None`.
+    fn reverse(&mut self, max_steps: Option<usize>, log: &mut String) -> Stop<W> {
+        let mut steps = 0;
+        loop {
+            if let Some(max) = max_steps {
+                if steps == max {
+                    return Stop::Steps;
+                }
+            } else if steps > 0 && self.at_breakpoint() {
+                return Stop::Breakpoint;
+            }
+            let new: Vec<W> = match self.history.last() {
+                Some(change) => change.writes.iter().map(|(addr, _)| self.cpu.mem[*addr].clone()).collect(),
+                None => return Stop::Oldest,
+            };
+            let change = self.history.undo(&mut self.cpu).unwrap();
+            let mut watched = false;
+            for ((addr, old), new) in change.writes.into_iter().zip(new) {
+                if self.watcher.watched(addr, true) {
+                    let event = Event::Write { pc: change.pc, addr, old, new };
+                    log.push_str(&format!("{}\n", event));
+                    watched = true;
+                }
+            }
+            steps += 1;
+            if watched && max_steps.is_none() {
+                return Stop::Watchpoint;
+            }
+        }
+    }
+
+    fn run_command(&mut self, max_steps: Option<usize>, backwards: bool) -> String {
+        let mut log = String::new();
+        let stop = if backwards {
+            self.reverse(max_steps, &mut log)
+        } else {
+            self.execute(max_steps, &mut log)
+        };
+        let reason = match stop {
+            Stop::Breakpoint => format!("breakpoint at {}", self.cpu.pc),
+            Stop::Watchpoint => "watchpoint".to_string(),
+            Stop::Steps => String::new(),
+            Stop::Oldest => "no earlier steps recorded".to_string(),
+            Stop::State(State::Halted) => "halted".to_string(),
+            Stop::State(State::NeedsInput) => "waiting for input".to_string(),
+            Stop::State(state) => format!("{:?}", state),
+            Stop::Error(err) => format!("error: {}", err),
+        };
+        if !reason.is_empty() {
+            log.push_str(&reason);
+            log.push('\n');
+        }
+        log.push_str(&self.current());
+        log
+    }
+
+    fn parse<T: std::str::FromStr>(arg: Option<&str>, what: &str) -> Result<T, String> {
+        let arg = arg.ok_or(format!("missing {}", what))?;
+        arg.parse().map_err(|_| format!("bad {}: `{}`", what, arg))
+    }
+
+    fn breakpoint_command(&mut self, arg: Option<&str>, add: bool) -> Result<String, String> {
+        let arg = match arg {
+            Some(arg) => arg,
+            None if add => {
+                let mut list: Vec<String> = self.breakpoints.iter().map(|b| b.to_string()).collect();
+                list.extend(self.op_breakpoints.iter().map(|op| op.mnemonic().to_string()));
+                return Ok(if list.is_empty() { "no breakpoints".to_string() } else { list.join(" ") });
+            }
+            None => return Err("missing address or opcode".to_string()),
+        };
+        if let Some(op) = Op::from_mnemonic(arg) {
+            self.op_breakpoints.retain(|&other| other != op);
+            if add {
+                self.op_breakpoints.push(op);
+            }
+        } else {
+            let addr = Self::parse(Some(arg), "address")?;
+            if add {
+                self.breakpoints.insert(addr);
+            } else {
+                self.breakpoints.remove(&addr);
+            }
+        }
+        Ok(String::new())
+    }
+
+    fn watch_command(&mut self, arg: Option<&str>, access: Option<&str>) -> Result<String, String> {
+        let arg = match arg {
+            Some(arg) => arg,
+            None => {
+                let list: Vec<String> = self.watcher.watchpoints.iter()
+                    .map(|w| format!("{}..{} {}{}", w.range.start, w.range.end,
+                                     if w.read { "r" } else { "" }, if w.write { "w" } else { "" }))
+                    .collect();
+                return Ok(if list.is_empty() { "no watchpoints".to_string() } else { list.join("\n") });
+            }
+        };
+        let range = match arg.split_once("..") {
+            Some((start, end)) => Self::parse(Some(start), "address")?..Self::parse(Some(end), "address")?,
+            None => {
+                let addr: usize = Self::parse(Some(arg), "address")?;
+                match addr.checked_add(1) {
+                    Some(end) if addr < self.cpu.mem.limit() => addr..end,
+                    _ => return Err(format!("address {} is outside of memory", addr)),
+                }
+            }
+        };
+        let (read, write) = match access.unwrap_or("w") {
+            "r" => (true, false),
+            "w" => (false, true),
+            "rw" | "wr" => (true, true),
+            other => return Err(format!("bad access `{}`, expected r, w or rw", other)),
+        };
+        self.watcher.watch(range, read, write);
+        Ok(String::new())
+    }
+
+    fn info(&self) -> String {
+        let inputs: Vec<String> = self.cpu.inputs.iter().map(|x| x.to_string()).collect();
+        format!("pc: {}  rb: {}  inputs: [{}]  outputs: {}\n{}",
+                self.cpu.pc, self.cpu.base_offset, inputs.join(", "),
+                self.cpu.outputs.len(), self.current())
+    }
+
+    /// Runs one command line and returns the text to show for it.
+    pub fn command(&mut self, line: &str) -> Result<String, String> {
+        let mut words = line.split_whitespace();
+        let cmd = match words.next() {
+            Some(cmd) => cmd,
+            None => return Ok(String::new()),
+        };
+        let arg = words.next();
+        match cmd {
+            "s" | "step" | "rs" | "rstep" => {
+                let n = match arg {
+                    Some(_) => Self::parse(arg, "count")?,
+                    None => 1,
+                };
+                Ok(self.run_command(Some(n), cmd.starts_with('r')))
+            }
+            "c" | "continue" => Ok(self.run_command(None, false)),
+            "rc" | "rcontinue" => Ok(self.run_command(None, true)),
+            "b" | "break" => self.breakpoint_command(arg, true),
+            "d" | "delete" => self.breakpoint_command(arg, false),
+            "x" => {
+                let addr: usize = Self::parse(arg, "address")?;
+                let n = match words.next() {
+                    Some(n) => Self::parse(Some(n), "count")?,
+                    None => 8,
+                };
+                if n > MAX_SHOWN {
+                    return Err(format!("at most {} words can be shown at once", MAX_SHOWN));
+                }
+                if addr.checked_add(n).is_none_or(|end| end > self.cpu.mem.limit()) {
+                    return Err(format!("{} words from address {} run outside of memory", n, addr));
+                }
+                let words: Vec<String> = self.cpu.mem.read(addr, n).iter().map(|x| x.to_string()).collect();
+                Ok(format!("{:>5}: {}", addr, words.join(", ")))
+            }
+            "set" => {
+                let addr: usize = Self::parse(arg, "address")?;
+                let value: W = Self::parse(words.next(), "value")?;
+                if addr >= self.cpu.mem.limit() {
+                    return Err(format!("address {} is outside of memory", addr));
+                }
+                self.cpu.mem[addr] = value;
+                self.history.clear();
+                Ok(String::new())
+            }
+            "pc" => {
+                if arg.is_some() {
+                    self.cpu.pc = Self::parse(arg, "address")?;
+                    self.history.clear();
+                }
+                Ok(self.current())
+            }
+            "rb" => {
+                if arg.is_some() {
+                    self.cpu.base_offset = Self::parse(arg, "relative base")?;
+                    self.history.clear();
+                }
+                Ok(format!("rb: {}", self.cpu.base_offset))
+            }
+            "in" => {
+                let mut values = Vec::new();
+                for word in arg.into_iter().chain(words) {
+                    values.push(Self::parse::<W>(Some(word), "input")?);
+                }
+                self.cpu.inputs.extend(values);
+                Ok(String::new())
+            }
+            "out" => {
+                let outputs: Vec<String> = self.cpu.outputs.iter().map(|x| x.to_string()).collect();
+                Ok(format!("[{}]", outputs.join(", ")))
+            }
+            "clear" => {
+                self.cpu.outputs.clear();
+                Ok(String::new())
+            }
+            "l" | "list" => {
+                let mut addr = match arg {
+                    Some(_) => Self::parse(arg, "address")?,
+                    None => self.cpu.pc,
+                };
+                let n: usize = match words.next() {
+                    Some(n) => Self::parse(Some(n), "count")?,
+                    None => 8,
+                };
+                if n > MAX_SHOWN {
+                    return Err(format!("at most {} items can be listed at once", MAX_SHOWN));
+                }
+                if addr >= self.cpu.mem.limit() {
+                    return Err(format!("address {} is outside of memory", addr));
+                }
+                let mut lines = Vec::new();
+                for _ in 0..n {
+                    let item = decode_mem(&self.cpu.mem, addr);
+                    let marker = if addr == self.cpu.pc { "=>" } else { "  " };
+                    lines.push(format!("{}{}", marker, item));
+                    match addr.checked_add(item.len()) {
+                        Some(next) if next < self.cpu.mem.limit() => addr = next,
+                        _ => break,
+                    }
+                }
+                Ok(lines.join("\n"))
+            }
+            "w" | "watch" => self.watch_command(arg, words.next()),
+            "unwatch" => {
+                self.watcher.unwatch(Self::parse(arg, "address")?);
+                Ok(String::new())
+            }
+            "smc" => {
+                self.watcher.detect_code_writes = match arg {
+                    Some("on") => true,
+                    Some("off") => false,
+                    _ => return Err("expected `smc on` or `smc off`".to_string()),
+                };
+                Ok(String::new())
+            }
+            "i" | "info" => Ok(self.info()),
+            "h" | "help" => Ok(HELP.to_string()),
+            _ => Err(format!("unknown command `{}`, try `help`", cmd)),
+        }
+    }
+}
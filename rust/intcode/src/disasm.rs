@@ -0,0 +1,158 @@
+use std::fmt;
+
+use crate::op::{self, Op, ParamMode};
+use crate::word::Word;
+
+// how many data words go on a single `db` line
+const DATA_PER_LINE: usize = 8;
+
+/// One line of a listing: either a decoded instruction or a run of words
+/// that were never reached as code.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Item<W = i128> {
+    Instr { addr: usize, op: Op, modes: [ParamMode; 3], params: Vec<W> },
+    Data { addr: usize, words: Vec<W> },
+}
+
+impl<W: Word> Item<W> {
+    pub fn addr(&self) -> usize {
+        match self {
+            Item::Instr { addr, .. } | Item::Data { addr, .. } => *addr,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Item::Instr { params, .. } => params.len() + 1,
+            Item::Data { words, .. } => words.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The item without its address column, e.g. `ADD [100], #1, [100]`.
+    pub fn text(&self) -> String {
+        match self {
+            Item::Instr { op, modes, params, .. } => {
+                let operands: Vec<String> = params.iter()
+                    .zip(modes.iter())
+                    .map(|(param, mode)| mode.format(param))
+                    .collect();
+                format!("{:<4} {}", op.mnemonic(), operands.join(", ")).trim_end().to_string()
+            }
+            Item::Data { words, .. } => {
+                let words: Vec<String> = words.iter().map(|x| x.to_string()).collect();
+                format!("{:<4} {}", "db", words.join(", "))
+            }
+        }
+    }
+}
+
+impl<W: Word> fmt::Display for Item<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:>5}: {}", self.addr(), self.text())
+    }
+}
+
+/// Decodes the instruction at `addr` if it is a canonically encoded
+/// instruction that fits in the program. Words with mode digits on
+/// parameters the op does not take are not treated as code.
+pub fn decode_at<W: Word>(program: &[W], addr: usize) -> Option<Item<W>> {
+    let (modes, op) = op::unpack_instr(program.get(addr)?).ok()?;
+    if program[addr].to_i128() != Some(op::pack_instr(op, &modes) as i128) {
+        return None;
+    }
+    let params = program.get(addr + 1..addr + 1 + op.n_params())?.to_vec();
+    Some(Item::Instr { addr, op, modes, params })
+}
+
+fn immediate_target<W: Word>(mode: ParamMode, param: &W) -> Option<usize> {
+    match (mode, param.to_i128()) {
+        (ParamMode::Immediate, Some(x)) if x >= 0 => usize::try_from(x).ok(),
+        _ => None,
+    }
+}
+
+/// Addresses the program can continue at after the instruction, as far
+/// as they are known statically. Jumps through memory only yield their
+/// fall-through address.
+pub fn successors<W: Word>(item: &Item<W>) -> Vec<usize> {
+    let (addr, op, modes, params) = match item {
+        Item::Instr { addr, op, modes, params } => (*addr, *op, modes, params),
+        Item::Data { .. } => return Vec::new(),
+    };
+    let next = addr + item.len();
+    match op {
+        Op::Halt => vec![],
+        Op::JmpIfTrue | Op::JmpIfFalse => {
+            let target = immediate_target(modes[1], &params[1]);
+            let taken = match modes[0] {
+                ParamMode::Immediate => Some(params[0].is_zero() == (op == Op::JmpIfFalse)),
+                _ => None,
+            };
+            match (taken, target) {
+                (Some(true), Some(target)) => vec![target],
+                (Some(true), None) => vec![],
+                (Some(false), _) => vec![next],
+                (None, Some(target)) => vec![next, target],
+                (None, None) => vec![next],
+            }
+        }
+        _ => vec![next],
+    }
+}
+
+/// Marks the addresses where instructions start, by following fall-through
+/// and immediate jump targets from address 0.
+pub fn code_starts<W: Word>(program: &[W]) -> Vec<bool> {
+    code_starts_from(program, &[0])
+}
+
+/// Like `code_starts`, but follows code from every one of `entries`, e.g.
+/// addresses known to have executed.
+pub fn code_starts_from<W: Word>(program: &[W], entries: &[usize]) -> Vec<bool> {
+    let mut starts = vec![false; program.len()];
+    let mut todo = entries.to_vec();
+    while let Some(addr) = todo.pop() {
+        if addr >= program.len() || starts[addr] {
+            continue;
+        }
+        if let Some(item) = decode_at(program, addr) {
+            starts[addr] = true;
+            todo.extend(successors(&item));
+        }
+    }
+    starts
+}
+
+pub fn disassemble<W: Word>(program: &[W]) -> Vec<Item<W>> {
+    disassemble_from(program, &[0])
+}
+
+/// Disassembles with code followed from each of `entries`.
+pub fn disassemble_from<W: Word>(program: &[W], entries: &[usize]) -> Vec<Item<W>> {
+    let starts = code_starts_from(program, entries);
+    let mut items = Vec::new();
+    let mut addr = 0;
+    while addr < program.len() {
+        if starts[addr] {
+            let item = decode_at(program, addr).unwrap();
+            addr += item.len();
+            items.push(item);
+            continue;
+        }
+        let start = addr;
+        while addr < program.len() && !starts[addr] && addr - start < DATA_PER_LINE {
+            addr += 1;
+        }
+        items.push(Item::Data { addr: start, words: program[start..addr].to_vec() });
+    }
+    items
+}
+
+/// The whole program as text, one item per line.
+pub fn listing<W: Word>(program: &[W]) -> String {
+    disassemble(program).iter().map(|item| format!("{}\n", item)).collect()
+}
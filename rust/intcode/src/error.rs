@@ -0,0 +1,61 @@
+use std::fmt;
+
+use crate::word::Word;
+
+/// Reasons a program can fail to run. Every variant records the pc of the
+/// faulting instruction and, where it could be fetched, its raw word.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Error<W = i128> {
+    PcOutOfRange { pc: usize },
+    UnknownOpcode { pc: usize, instr: W },
+    UnknownParamMode { pc: usize, instr: W },
+    NegativeAddress { pc: usize, instr: W, addr: W },
+    AddressOutOfRange { pc: usize, instr: W, addr: W },
+    ImmediateWrite { pc: usize, instr: W },
+    Overflow { pc: usize, instr: W },
+}
+
+impl<W> Error<W> {
+    pub fn pc(&self) -> usize {
+        match *self {
+            Error::PcOutOfRange { pc }
+            | Error::UnknownOpcode { pc, .. }
+            | Error::UnknownParamMode { pc, .. }
+            | Error::NegativeAddress { pc, .. }
+            | Error::AddressOutOfRange { pc, .. }
+            | Error::ImmediateWrite { pc, .. }
+            | Error::Overflow { pc, .. } => pc,
+        }
+    }
+}
+
+impl<W: Word> fmt::Display for Error<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::PcOutOfRange { pc } => {
+                write!(f, "pc {} is outside of memory", pc)
+            }
+            Error::UnknownOpcode { pc, instr } => {
+                write!(f, "unknown opcode in {} at pc {}", instr, pc)
+            }
+            Error::UnknownParamMode { pc, instr } => {
+                write!(f, "unknown parameter mode in {} at pc {}", instr, pc)
+            }
+            Error::NegativeAddress { pc, instr, addr } => {
+                write!(f, "negative address {} used by {} at pc {}", addr, instr, pc)
+            }
+            Error::AddressOutOfRange { pc, instr, addr } => {
+                write!(f, "address {} used by {} at pc {} is outside of memory",
+                       addr, instr, pc)
+            }
+            Error::ImmediateWrite { pc, instr } => {
+                write!(f, "write to an immediate parameter in {} at pc {}", instr, pc)
+            }
+            Error::Overflow { pc, instr } => {
+                write!(f, "arithmetic overflow in {} at pc {}", instr, pc)
+            }
+        }
+    }
+}
+
+impl<W: Word> std::error::Error for Error<W> {}
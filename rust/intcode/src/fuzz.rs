@@ -0,0 +1,452 @@
+//! Differential fuzzing of the ways this crate can run a program.
+//!
+//! A `Fuzzer` generates random, canonically encoded programs with inputs,
+//! runs each of them on every `Backend` within a step budget and compares
+//! what they ended up with. The first case on which the backends disagree
+//! is shrunk to a smaller program that still makes them disagree.
+//!
+//! Besides the `Cpu` itself, the backends run the program on other word
+//! types and after `optimize`, each converting what it got back to what a
+//! `Cpu` would have. Cases a backend can not tell anything about, like a
+//! program that does not fit its words, are left to the others.
+//!
+//! Everything is derived from the seed, so a run can be repeated exactly,
+//! and case `n` of a seed can be regenerated on its own with `case`.
+
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::aot;
+use crate::bigint::BigInt;
+use crate::cpu::{Cpu, Observer, State};
+use crate::error::Error;
+use crate::limits::{Guard, LimitError, Limits};
+use crate::op::{self, Op, ParamMode, ALL_OPS};
+use crate::optimize::{self, Options};
+use crate::search::Runnable;
+use crate::word::Word;
+
+// candidate programs the shrinker may try for one mismatch
+const MAX_SHRINK_RUNS: usize = 20_000;
+
+/// splitmix64, which is plenty for generating test programs.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    // true once in `n` times
+    fn one_in(&mut self, n: usize) -> bool {
+        self.below(n) == 0
+    }
+
+    fn range(&mut self, lo: i128, hi: i128) -> i128 {
+        lo + (self.next() as i128).rem_euclid(hi - lo + 1)
+    }
+}
+
+/// How a run ended and what it left behind.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Outcome {
+    pub state: Result<State, Error>,
+    pub outputs: Vec<i128>,
+    /// Every nonzero memory cell, by address, if the backend leaves memory
+    /// as a `Cpu` would.
+    pub mem: Option<Vec<(usize, i128)>>,
+}
+
+impl Outcome {
+    /// Whether both ended the same way, comparing memory only if both know
+    /// it.
+    pub fn agrees(&self, other: &Outcome) -> bool {
+        self.state == other.state
+            && self.outputs == other.outputs
+            && (self.mem.is_none() || other.mem.is_none() || self.mem == other.mem)
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.state {
+            Ok(state) => write!(f, "{:?}", state)?,
+            Err(err) => write!(f, "error: {}", err)?,
+        }
+        write!(f, ", outputs {:?}", self.outputs)?;
+        if let Some(mem) = &self.mem {
+            write!(f, ", memory")?;
+            for (addr, x) in mem {
+                write!(f, " {}={}", addr, x)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// what a `Cpu` would have ended up with, if every word fits in an i128
+fn widen<W: Word>(state: Result<State<W>, Error<W>>, cpu: &Cpu<W>) -> Option<Outcome> {
+    let word = |x: &W| x.to_i128();
+    let state = match state {
+        Ok(State::Running) => Ok(State::Running),
+        Ok(State::Output(x)) => Ok(State::Output(word(&x)?)),
+        Ok(State::NeedsInput) => Ok(State::NeedsInput),
+        Ok(State::Halted) => Ok(State::Halted),
+        Ok(State::StepLimitReached) => Ok(State::StepLimitReached),
+        Err(err) => Err(match err {
+            Error::PcOutOfRange { pc } => Error::PcOutOfRange { pc },
+            Error::UnknownOpcode { pc, instr } => Error::UnknownOpcode { pc, instr: word(&instr)? },
+            Error::UnknownParamMode { pc, instr } => Error::UnknownParamMode { pc, instr: word(&instr)? },
+            Error::NegativeAddress { pc, instr, addr } => {
+                Error::NegativeAddress { pc, instr: word(&instr)?, addr: word(&addr)? }
+            }
+            Error::AddressOutOfRange { pc, instr, addr } => {
+                Error::AddressOutOfRange { pc, instr: word(&instr)?, addr: word(&addr)? }
+            }
+            Error::ImmediateWrite { pc, instr } => Error::ImmediateWrite { pc, instr: word(&instr)? },
+            Error::Overflow { pc, instr } => Error::Overflow { pc, instr: word(&instr)? },
+        }),
+    };
+    let mut mem = Vec::new();
+    for (start, page) in cpu.mem.pages() {
+        for (n, x) in page.iter().enumerate().filter(|(_, x)| !x.is_zero()) {
+            mem.push((start + n, word(x)?));
+        }
+    }
+    let outputs = cpu.outputs.iter().map(word).collect::<Option<_>>()?;
+    Some(Outcome { state, outputs, mem: Some(mem) })
+}
+
+/// Feeds `inputs` to `machine` and runs it for at most `max_steps`.
+pub fn outcome<M: Runnable>(mut machine: M, inputs: &[i128], max_steps: usize) -> Outcome {
+    machine.cpu().inputs.extend(inputs);
+    let state = machine.run_for(max_steps);
+    widen(state, machine.cpu()).unwrap()
+}
+
+/// One way of running a program. It returns `None` for cases it can not
+/// tell anything about.
+#[derive(Clone, Copy)]
+pub struct Backend {
+    pub name: &'static str,
+    pub run: fn(program: &[i128], inputs: &[i128], max_steps: usize) -> Option<Outcome>,
+}
+
+fn guarded(program: &[i128], inputs: &[i128], max_steps: usize) -> Option<Outcome> {
+    let mut cpu = Cpu::new(program);
+    cpu.inputs.extend(inputs);
+    let mut guard = Guard::new(Limits { steps: Some(max_steps as u64), ..Limits::default() });
+    let state = match guard.run(&mut cpu) {
+        Ok(state) => Ok(state),
+        Err(LimitError::Steps { .. }) => Ok(State::StepLimitReached),
+        Err(LimitError::Machine(err)) => Err(err),
+        Err(err) => unreachable!("{}", err),
+    };
+    widen(state, &cpu)
+}
+
+// the optimized program takes fewer steps and keeps less data around, so
+// only finished runs are compared, and not by memory
+fn optimized(program: &[i128], inputs: &[i128], max_steps: usize) -> Option<Outcome> {
+    let (program, _) = optimize::optimize(program, Options::default());
+    let outcome = outcome(Cpu::new(&program), inputs, max_steps);
+    if outcome.state == Ok(State::StepLimitReached) {
+        return None;
+    }
+    Some(Outcome { mem: None, ..outcome })
+}
+
+// runs that overflow an i64 are no use, as they may well fit an i128
+fn narrow(program: &[i128], inputs: &[i128], max_steps: usize) -> Option<Outcome> {
+    let narrow = |words: &[i128]| words.iter().map(|&x| i64::try_from(x).ok()).collect::<Option<Vec<i64>>>();
+    let mut cpu = Cpu::new(&narrow(program)?);
+    cpu.inputs.extend(narrow(inputs)?);
+    let state = cpu.run_for(max_steps);
+    if matches!(state, Err(Error::Overflow { .. })) {
+        return None;
+    }
+    widen(state, &cpu)
+}
+
+// the first write of a value that does not fit in an i128, and the word it
+// replaced
+#[derive(Default)]
+struct TooWide(Option<(usize, BigInt)>);
+
+impl Observer<BigInt> for TooWide {
+    fn write(&mut self, _pc: usize, addr: usize, old: &BigInt, new: &BigInt) {
+        if new.to_i128().is_none() {
+            self.0 = Some((addr, old.clone()));
+        }
+    }
+}
+
+// never overflows, so an i128 overflow is caught as the write of a value
+// that does not fit, which is then taken back
+fn wide(program: &[i128], inputs: &[i128], max_steps: usize) -> Option<Outcome> {
+    let mut cpu = Cpu::new(&program.iter().map(|&x| BigInt::from(x)).collect::<Vec<_>>());
+    cpu.inputs.extend(inputs.iter().map(|&x| BigInt::from(x)));
+    let mut state = Ok(State::StepLimitReached);
+    for _ in 0..max_steps {
+        let (pc, instr) = (cpu.pc, cpu.mem[cpu.pc].clone());
+        let mut too_wide = TooWide::default();
+        match cpu.step_with(&mut too_wide) {
+            _ if too_wide.0.is_some() => {
+                let (addr, old) = too_wide.0.unwrap();
+                cpu.mem[addr] = old;
+                state = Err(Error::Overflow { pc, instr });
+                break;
+            }
+            // an i128 may have overflowed before the failing store or
+            // relative address, which goes unseen here
+            Err(_) if matches!(op::unpack_instr(&instr), Ok((_, Op::Add | Op::Mul))) => return None,
+            Ok(State::Running) => continue,
+            Ok(State::Output(x)) => cpu.outputs.push(x),
+            other => {
+                state = other;
+                break;
+            }
+        }
+    }
+    widen(state, &cpu)
+}
+
+/// Every backend that can run a program only known at run time. Compiled
+/// `aot` modules only exist for programs known at build time.
+pub fn backends() -> Vec<Backend> {
+    vec![
+        Backend { name: "cpu", run: |program, inputs, max_steps| Some(outcome(Cpu::new(program), inputs, max_steps)) },
+        Backend {
+            name: "cpu without code cache",
+            run: |program, inputs, max_steps| {
+                let mut cpu = Cpu::new(program);
+                cpu.mem.set_code_cache(false);
+                Some(outcome(cpu, inputs, max_steps))
+            },
+        },
+        Backend {
+            name: "aot machine",
+            run: |program, inputs, max_steps| Some(outcome(aot::Machine::interpreted(program), inputs, max_steps)),
+        },
+        Backend { name: "guard", run: guarded },
+        Backend { name: "optimized cpu", run: optimized },
+        Backend { name: "i64 cpu", run: narrow },
+        Backend { name: "bigint cpu", run: wide },
+    ]
+}
+
+fn is_jump_target(op: Op, n: usize) -> bool {
+    matches!(op, Op::JmpIfTrue | Op::JmpIfFalse) && n == 1
+}
+
+/// A generated program and its inputs.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Case {
+    pub program: Vec<i128>,
+    pub inputs: Vec<i128>,
+}
+
+/// Backends that disagree on a case, with what each of them did.
+#[derive(Clone, Debug)]
+pub struct Mismatch {
+    /// Number of the case the mismatch was found in.
+    pub case: u64,
+    /// The smallest version of the case found that still shows it.
+    pub shrunk: Case,
+    pub outcomes: Vec<(&'static str, Outcome)>,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let join = |xs: &[i128]| xs.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(",");
+        writeln!(f, "backends disagree on case {}", self.case)?;
+        writeln!(f, "program: {}", join(&self.shrunk.program))?;
+        writeln!(f, "inputs: {}", join(&self.shrunk.inputs))?;
+        for (name, outcome) in &self.outcomes {
+            writeln!(f, "{}: {}", name, outcome)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs generated cases on several backends.
+pub struct Fuzzer {
+    pub seed: u64,
+    /// Instructions in a generated program, at most.
+    pub max_instrs: usize,
+    pub max_steps: usize,
+    backends: Vec<Backend>,
+}
+
+impl Fuzzer {
+    /// A fuzzer comparing all of `backends()`.
+    pub fn new(seed: u64) -> Fuzzer {
+        Fuzzer { seed, max_instrs: 24, max_steps: 1000, backends: backends() }
+    }
+
+    /// Compares only the given backends instead.
+    pub fn with_backends(seed: u64, backends: Vec<Backend>) -> Fuzzer {
+        Fuzzer { backends, ..Fuzzer::new(seed) }
+    }
+
+    fn rng(&self, n: u64) -> Rng {
+        let mut rng = Rng(self.seed ^ n.wrapping_mul(0xd1b5_4a32_d192_ed03));
+        rng.next();
+        rng
+    }
+
+    /// Case number `n` of the seed.
+    pub fn case(&self, n: u64) -> Case {
+        let mut rng = self.rng(n);
+
+        let n_instrs = 1 + rng.below(self.max_instrs);
+        let instrs: Vec<(Op, [ParamMode; 3])> = (0..n_instrs)
+            .map(|_| {
+                // halt only at the end, and do some more arithmetic
+                let op = match rng.below(ALL_OPS.len() + 4) {
+                    i if i < ALL_OPS.len() && ALL_OPS[i] != Op::Halt => ALL_OPS[i],
+                    _ => Op::Add,
+                };
+                let mut modes = [ParamMode::Position; 3];
+                for (n, mode) in modes.iter_mut().take(op.n_params()).enumerate() {
+                    *mode = match (rng.below(3), Some(n) == op.out_param()) {
+                        // jumps mostly go to instructions
+                        _ if is_jump_target(op, n) && !rng.one_in(8) => ParamMode::Immediate,
+                        (0, false) => ParamMode::Immediate,
+                        (1, _) => ParamMode::Relative,
+                        _ => ParamMode::Position,
+                    };
+                }
+                (op, modes)
+            })
+            .collect();
+        let mut starts = Vec::new();
+        let mut len = 0;
+        for (op, _) in &instrs {
+            starts.push(len);
+            len += 1 + op.n_params();
+        }
+        let data = 1 + rng.below(8);
+        let size = (len + 1 + data) as i128;
+
+        let mut program = Vec::new();
+        for (op, modes) in &instrs {
+            program.push(op::pack_instr(*op, modes) as i128);
+            for (n, mode) in modes.iter().enumerate().take(op.n_params()) {
+                program.push(match mode {
+                    ParamMode::Immediate if is_jump_target(*op, n) => starts[rng.below(starts.len())] as i128,
+                    ParamMode::Immediate if *op == Op::AdjustRelBase => rng.range(-4, 8),
+                    ParamMode::Immediate if rng.one_in(16) => rng.range(-1 << 70, 1 << 70),
+                    ParamMode::Immediate => rng.range(-5, 20),
+                    ParamMode::Position if rng.one_in(200) => -1,
+                    // mostly the data after the code
+                    ParamMode::Position if rng.one_in(8) => rng.range(0, size - 1),
+                    ParamMode::Position => rng.range(len as i128 + 1, size - 1),
+                    ParamMode::Relative if rng.one_in(100) => rng.range(-4, -1),
+                    ParamMode::Relative => rng.range(0, size - 1),
+                });
+            }
+        }
+        program.push(99);
+        program.extend((0..data).map(|_| rng.range(-5, 20)));
+
+        let inputs = (0..rng.below(5)).map(|_| rng.range(-3, 9)).collect();
+        Case { program, inputs }
+    }
+
+    // outcomes of the backends that could run the case
+    fn outcomes(&self, case: &Case) -> Vec<(&'static str, Outcome)> {
+        self.backends.iter()
+            .filter_map(|backend| Some((backend.name, (backend.run)(&case.program, &case.inputs, self.max_steps)?)))
+            .collect()
+    }
+
+    fn disagree(&self, case: &Case) -> bool {
+        let outcomes = self.outcomes(case);
+        outcomes.iter().any(|(_, outcome)| !outcome.agrees(&outcomes[0].1))
+    }
+
+    /// A smaller case the backends still disagree on: inputs and words are
+    /// removed and words moved towards zero while that is the case.
+    pub fn shrink(&self, case: &Case) -> Case {
+        let mut best = case.clone();
+        let mut runs = 0;
+        let mut try_case = |candidate: Case, best: &mut Case| {
+            runs += 1;
+            let smaller = candidate != *best && runs <= MAX_SHRINK_RUNS && self.disagree(&candidate);
+            if smaller {
+                *best = candidate;
+            }
+            smaller
+        };
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for i in (0..best.inputs.len()).rev() {
+                let mut candidate = best.clone();
+                candidate.inputs.remove(i);
+                changed |= try_case(candidate, &mut best);
+            }
+            for len in 0..best.program.len() {
+                let mut candidate = best.clone();
+                candidate.program.truncate(len);
+                if try_case(candidate, &mut best) {
+                    changed = true;
+                    break;
+                }
+            }
+            for i in (0..best.program.len()).rev() {
+                let mut candidate = best.clone();
+                candidate.program.remove(i);
+                if try_case(candidate, &mut best) {
+                    changed = true;
+                    continue;
+                }
+                for x in [0, best.program[i] / 2] {
+                    let mut candidate = best.clone();
+                    candidate.program[i] = x;
+                    if try_case(candidate, &mut best) {
+                        changed = true;
+                        break;
+                    }
+                }
+            }
+        }
+        best
+    }
+
+    /// Runs cases `0..cases` and returns the first mismatch, shrunk.
+    pub fn run(&self, cases: u64) -> Option<Mismatch> {
+        let case = (0..cases).find(|&n| self.disagree(&self.case(n)))?;
+        let shrunk = self.shrink(&self.case(case));
+        let outcomes = self.outcomes(&shrunk);
+        Some(Mismatch { case, shrunk, outcomes })
+    }
+
+    /// Compares `machine`, an `aot` module compiled at build time, with a
+    /// `Cpu` running its program, on cases `0..cases` of up to `max_inputs`
+    /// inputs taken from `values`. The first mismatch is not shrunk.
+    pub fn run_compiled(&self, machine: fn() -> aot::Machine, max_inputs: usize, values: RangeInclusive<i128>, cases: u64) -> Option<Mismatch> {
+        let cpu = machine().into_cpu();
+        let program = cpu.mem.read(0, cpu.mem.extent());
+        (0..cases).find_map(|case| {
+            let mut rng = self.rng(case);
+            let n_inputs = rng.below(max_inputs + 1);
+            let inputs: Vec<i128> = (0..n_inputs).map(|_| rng.range(*values.start(), *values.end())).collect();
+            let outcomes = vec![
+                ("cpu", outcome(cpu.clone(), &inputs, self.max_steps)),
+                ("compiled", outcome(machine(), &inputs, self.max_steps)),
+            ];
+            let shrunk = Case { program: program.clone(), inputs };
+            (outcomes[0].1 != outcomes[1].1).then_some(Mismatch { case, shrunk, outcomes })
+        })
+    }
+}
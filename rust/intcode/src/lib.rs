@@ -0,0 +1,48 @@
+//! Shared Intcode interpreter used by every day that runs an Intcode
+//! program (d02, d05, d07, d09, d11).
+
+use std::str::FromStr;
+
+pub mod aot;
+pub mod ascii;
+pub mod asm;
+mod bigint;
+pub mod cfg;
+mod cpu;
+pub mod debugger;
+pub mod disasm;
+mod error;
+pub mod fuzz;
+pub mod limits;
+mod memory;
+mod op;
+pub mod optimize;
+pub mod packet;
+pub mod profile;
+pub mod search;
+mod snapshot;
+pub mod symbolic;
+pub mod task;
+pub mod threads;
+pub mod trace;
+pub mod undo;
+pub mod watch;
+mod word;
+
+pub use bigint::{BigInt, ParseBigIntError};
+pub use cpu::{Cpu, Observer, State};
+pub use error::Error;
+pub use memory::Memory;
+pub use op::{pack_instr, unpack_instr, DecodeError, Op, ParamMode};
+pub use word::{Overflow, Word};
+
+/// Parses the comma-separated program format of the puzzle inputs.
+pub fn parse_program<W: Word>(text: &str) -> Result<Vec<W>, <W as FromStr>::Err> {
+    text.trim().split(',').map(|x| x.trim().parse()).collect()
+}
+
+/// Formats a program the way `parse_program` reads it.
+pub fn format_program<W: Word>(program: &[W]) -> String {
+    let words: Vec<String> = program.iter().map(|x| x.to_string()).collect();
+    words.join(",")
+}
@@ -0,0 +1,203 @@
+//! Budgets and loop detection, so a runaway program cannot hang the
+//! caller.
+//!
+//! A `Guard` drives a machine like `Cpu::run` does, but fails with a
+//! `LimitError` once it has used up its step or time budget. With loop
+//! detection on it also fails as soon as the machine comes back to a state
+//! it was in before without having consumed any input in between, since
+//! it would then go around that loop forever.
+//!
+//! A state is the pc, the relative base and memory. States are compared
+//! with Brent's algorithm, which keeps only one earlier state around and
+//! finds a loop within a few times its length. Memory is tracked as a hash
+//! of every write so far and compared in full only when the hashes match,
+//! so a collision is never taken for a loop.
+
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+use crate::cpu::{Cpu, Observer, State};
+use crate::error::Error;
+use crate::memory::Memory;
+use crate::word::Word;
+
+// steps between looks at the clock
+const CLOCK_INTERVAL: u64 = 1024;
+
+/// What a `Guard` allows. Everything is off by default.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct Limits {
+    pub steps: Option<u64>,
+    /// Time spent running, not counting time between calls.
+    pub time: Option<Duration>,
+    pub detect_loops: bool,
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum LimitError<W = i128> {
+    Machine(Error<W>),
+    Steps { pc: usize, steps: u64 },
+    Time { pc: usize, elapsed: Duration },
+    /// The state at `pc` repeats every `period` steps.
+    Loop { pc: usize, period: u64 },
+}
+
+impl<W: Word> fmt::Display for LimitError<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LimitError::Machine(err) => write!(f, "{}", err),
+            LimitError::Steps { pc, steps } => {
+                write!(f, "step budget of {} used up at pc {}", steps, pc)
+            }
+            LimitError::Time { pc, elapsed } => {
+                write!(f, "time budget used up after {:?} at pc {}", elapsed, pc)
+            }
+            LimitError::Loop { pc, period } => {
+                write!(f, "stuck in a loop of {} steps without input at pc {}", period, pc)
+            }
+        }
+    }
+}
+
+impl<W: Word> std::error::Error for LimitError<W> {}
+
+impl<W> From<Error<W>> for LimitError<W> {
+    fn from(err: Error<W>) -> LimitError<W> {
+        LimitError::Machine(err)
+    }
+}
+
+fn cell_hash<W: Hash>(addr: usize, x: &W) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    (addr, x).hash(&mut hasher);
+    hasher.finish()
+}
+
+// keeps a hash of memory as written so far, which is zero for the memory
+// the guard started with
+struct MemHash<'a>(&'a mut u64);
+
+impl<W: Hash> Observer<W> for MemHash<'_> {
+    fn write(&mut self, _pc: usize, addr: usize, old: &W, new: &W) {
+        *self.0 ^= cell_hash(addr, old) ^ cell_hash(addr, new);
+    }
+}
+
+// pc, relative base and memory hash
+type MachineState = (usize, i64, u64);
+
+/// Runs a machine within `Limits`. Budgets add up over every call. Loop
+/// detection only sees memory written by the machine itself, so use a new
+/// guard after patching memory by hand.
+#[derive(Clone)]
+pub struct Guard<W = i128> {
+    pub limits: Limits,
+    steps: u64,
+    elapsed: Duration,
+    mem_hash: u64,
+    // Brent's algorithm: the state `power` steps were last counted from,
+    // with its memory
+    saved: Option<(MachineState, Memory<W>)>,
+    power: u64,
+    period: u64,
+}
+
+impl<W> fmt::Debug for Guard<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Guard")
+            .field("limits", &self.limits)
+            .field("steps", &self.steps)
+            .field("elapsed", &self.elapsed)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<W: Word> Guard<W> {
+    pub fn new(limits: Limits) -> Guard<W> {
+        Guard { limits, steps: 0, elapsed: Duration::ZERO, mem_hash: 0, saved: None, power: 1, period: 0 }
+    }
+
+    /// Steps taken so far, not counting those that found the machine
+    /// waiting for input.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Time spent running so far.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// One step of `cpu`, if the step budget allows. Only the `run` methods
+    /// look at the time budget.
+    pub fn step(&mut self, cpu: &mut Cpu<W>) -> Result<State<W>, LimitError<W>> {
+        if self.limits.steps.is_some_and(|max| self.steps >= max) {
+            return Err(LimitError::Steps { pc: cpu.pc, steps: self.steps });
+        }
+        let inputs = cpu.inputs.len();
+        let state = if self.limits.detect_loops {
+            cpu.step_with(&mut MemHash(&mut self.mem_hash))?
+        } else {
+            cpu.step()?
+        };
+        // waiting for input runs no instruction
+        if state == State::NeedsInput {
+            return Ok(state);
+        }
+        self.steps += 1;
+        if !self.limits.detect_loops {
+            return Ok(state);
+        }
+        let current = (cpu.pc, cpu.base_offset, self.mem_hash);
+        let saved = match &self.saved {
+            Some((saved, mem)) if cpu.inputs.len() == inputs => (*saved, mem),
+            _ => {
+                self.saved = Some((current, cpu.mem.clone()));
+                self.power = 1;
+                self.period = 0;
+                return Ok(state);
+            }
+        };
+        self.period += 1;
+        if saved.0 == current && saved.1.same_words(&cpu.mem) {
+            return Err(LimitError::Loop { pc: cpu.pc, period: self.period });
+        }
+        if self.period == self.power {
+            self.saved = Some((current, cpu.mem.clone()));
+            self.power *= 2;
+            self.period = 0;
+        }
+        Ok(state)
+    }
+
+    fn run_while(&mut self, cpu: &mut Cpu<W>, buffer: bool) -> Result<State<W>, LimitError<W>> {
+        let start = Instant::now();
+        let result = loop {
+            if self.steps.is_multiple_of(CLOCK_INTERVAL) {
+                let elapsed = self.elapsed + start.elapsed();
+                if self.limits.time.is_some_and(|max| elapsed >= max) {
+                    break Err(LimitError::Time { pc: cpu.pc, elapsed });
+                }
+            }
+            match self.step(cpu) {
+                Ok(State::Running) => continue,
+                Ok(State::Output(x)) if buffer => cpu.outputs.push(x),
+                other => break other,
+            }
+        };
+        self.elapsed += start.elapsed();
+        result
+    }
+
+    /// `Cpu::run` within the limits.
+    pub fn run(&mut self, cpu: &mut Cpu<W>) -> Result<State<W>, LimitError<W>> {
+        self.run_while(cpu, true)
+    }
+
+    /// `Cpu::run_until_output` within the limits.
+    pub fn run_until_output(&mut self, cpu: &mut Cpu<W>) -> Result<State<W>, LimitError<W>> {
+        self.run_while(cpu, false)
+    }
+}
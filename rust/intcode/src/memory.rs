@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::ops::{Index, IndexMut};
+use std::sync::Arc;
+
+use crate::op::Decoded;
+use crate::word::Word;
+
+const PAGE_BITS: usize = 10;
+const PAGE_SIZE: usize = 1 << PAGE_BITS;
+// pages below this are kept in a flat table, the rest in a hash map
+const DIRECT_PAGES: usize = 4096;
+// instructions are only cached below this address
+const CACHED_CODE: usize = 1 << 16;
+
+// shared between clones until one of them writes to it
+type Page<W> = Arc<[W]>;
+
+/// Paged Intcode memory. Pages are allocated on first write, so reading
+/// untouched addresses costs nothing and yields 0. Cloning is cheap: pages
+/// are copied only when a clone writes to them, and a clone starts with an
+/// empty cache of decoded instructions.
+pub struct Memory<W = i128> {
+    direct: Vec<Option<Page<W>>>,
+    far: HashMap<usize, Page<W>>,
+    limit: usize,
+    extent: usize,
+    zero: W,
+    // decoded instructions by address; any write to one of their words
+    // drops them
+    decoded: Vec<Option<Decoded<W>>>,
+    cache_code: bool,
+}
+
+impl<W: Clone> Clone for Memory<W> {
+    fn clone(&self) -> Memory<W> {
+        Memory {
+            direct: self.direct.clone(),
+            far: self.far.clone(),
+            limit: self.limit,
+            extent: self.extent,
+            zero: self.zero.clone(),
+            decoded: Vec::new(),
+            cache_code: self.cache_code,
+        }
+    }
+}
+
+impl<W: Word> Memory<W> {
+    pub fn new(program: &[W]) -> Memory<W> {
+        let mut mem = Memory {
+            direct: Vec::new(),
+            far: HashMap::new(),
+            limit: usize::MAX,
+            extent: 0,
+            zero: W::default(),
+            decoded: Vec::new(),
+            cache_code: true,
+        };
+        for (addr, x) in program.iter().enumerate() {
+            mem[addr] = x.clone();
+        }
+        mem.extent = program.len();
+        mem
+    }
+
+    /// Addresses at or above the limit are outside of memory.
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        self.decoded.clear();
+    }
+
+    /// Turns the interpreter's cache of decoded instructions on or off. It
+    /// is on by default.
+    pub fn set_code_cache(&mut self, enabled: bool) {
+        self.cache_code = enabled;
+        self.decoded.clear();
+    }
+
+    /// Number of decoded instructions in the cache.
+    pub fn cached_instrs(&self) -> usize {
+        self.decoded.iter().filter(|decoded| decoded.is_some()).count()
+    }
+
+    pub(crate) fn decoded(&self, addr: usize) -> Option<&Decoded<W>> {
+        self.decoded.get(addr).and_then(|decoded| decoded.as_ref())
+    }
+
+    pub(crate) fn cache_decoded(&mut self, addr: usize, decoded: Decoded<W>) {
+        if !self.cache_code || addr >= CACHED_CODE {
+            return;
+        }
+        if self.decoded.len() <= addr {
+            self.decoded.resize_with(addr + 1, || None);
+        }
+        self.decoded[addr] = Some(decoded);
+    }
+
+    /// One past the highest address that was loaded or written.
+    pub fn extent(&self) -> usize {
+        self.extent
+    }
+
+    pub fn get(&self, addr: usize) -> W {
+        self[addr].clone()
+    }
+
+    pub fn set(&mut self, addr: usize, val: W) {
+        self[addr] = val;
+    }
+
+    /// Copies out `len` words starting at `start`, or fewer if they would
+    /// run past the highest address.
+    pub fn read(&self, start: usize, len: usize) -> Vec<W> {
+        (start..start.saturating_add(len)).map(|addr| self[addr].clone()).collect()
+    }
+
+    /// The allocated pages in address order, as their first address and
+    /// contents.
+    pub fn pages(&self) -> Vec<(usize, &[W])> {
+        let mut pages: Vec<(usize, &[W])> = self.direct.iter().enumerate()
+            .filter_map(|(n, page)| page.as_ref().map(|page| (n, &page[..])))
+            .chain(self.far.iter().map(|(&n, page)| (n, &page[..])))
+            .map(|(n, page)| (n << PAGE_BITS, page))
+            .collect();
+        pages.sort_by_key(|&(addr, _)| addr);
+        pages
+    }
+
+    /// Whether both hold the same words, however their pages happen to be
+    /// allocated.
+    pub(crate) fn same_words(&self, other: &Memory<W>) -> bool {
+        let numbers = |mem: &Memory<W>| -> Vec<usize> {
+            mem.direct.iter().enumerate()
+                .filter_map(|(n, page)| page.as_ref().map(|_| n))
+                .chain(mem.far.keys().copied())
+                .collect()
+        };
+        numbers(self).into_iter().chain(numbers(other)).all(|n| match (self.page(n), other.page(n)) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b) || a == b,
+            (Some(page), None) | (None, Some(page)) => page.iter().all(|x| *x == self.zero),
+            (None, None) => true,
+        })
+    }
+
+    pub(crate) fn set_extent(&mut self, extent: usize) {
+        self.extent = extent;
+    }
+
+    fn page(&self, n: usize) -> Option<&Page<W>> {
+        if n < DIRECT_PAGES {
+            self.direct.get(n).and_then(|page| page.as_ref())
+        } else {
+            self.far.get(&n)
+        }
+    }
+
+    fn page_mut(&mut self, n: usize) -> &mut [W] {
+        let new_page = || vec![W::default(); PAGE_SIZE].into();
+        let page = if n < DIRECT_PAGES {
+            if self.direct.len() <= n {
+                self.direct.resize_with(n + 1, || None);
+            }
+            self.direct[n].get_or_insert_with(new_page)
+        } else {
+            self.far.entry(n).or_insert_with(new_page)
+        };
+        Arc::make_mut(page)
+    }
+}
+
+impl<W: Word> Index<usize> for Memory<W> {
+    type Output = W;
+
+    fn index(&self, addr: usize) -> &W {
+        match self.page(addr >> PAGE_BITS) {
+            Some(page) => &page[addr & (PAGE_SIZE - 1)],
+            None => &self.zero,
+        }
+    }
+}
+
+impl<W: Word> IndexMut<usize> for Memory<W> {
+    fn index_mut(&mut self, addr: usize) -> &mut W {
+        self.extent = self.extent.max(addr.saturating_add(1));
+        // an instruction is at most 4 words long
+        if addr < self.decoded.len() + 3 {
+            let end = (addr + 1).min(self.decoded.len());
+            for decoded in &mut self.decoded[addr.saturating_sub(3)..end] {
+                *decoded = None;
+            }
+        }
+        let page = self.page_mut(addr >> PAGE_BITS);
+        &mut page[addr & (PAGE_SIZE - 1)]
+    }
+}
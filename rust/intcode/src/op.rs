@@ -0,0 +1,186 @@
+use crate::word::Word;
+
+const ADD_OP: u32 = 1;
+const MUL_OP: u32 = 2;
+const IN_OP: u32 = 3;
+const OUT_OP: u32 = 4;
+const JIFT_OP: u32 = 5;
+const JIFF_OP: u32 = 6;
+const LT_OP: u32 = 7;
+const EQ_OP: u32 = 8;
+const ARB_OP: u32 = 9;
+const HALT_OP: u32 = 99;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Op {
+    Add,
+    Mul,
+    Input,
+    Output,
+    JmpIfTrue,
+    JmpIfFalse,
+    LessThan,
+    Equals,
+    AdjustRelBase,
+    Halt,
+}
+
+impl Op {
+    pub fn from_value(op: u32) -> Option<Op> {
+        match op % 100 {
+            ADD_OP => Some(Op::Add),
+            MUL_OP => Some(Op::Mul),
+            IN_OP => Some(Op::Input),
+            OUT_OP => Some(Op::Output),
+            JIFT_OP => Some(Op::JmpIfTrue),
+            JIFF_OP => Some(Op::JmpIfFalse),
+            LT_OP => Some(Op::LessThan),
+            EQ_OP => Some(Op::Equals),
+            ARB_OP => Some(Op::AdjustRelBase),
+            HALT_OP => Some(Op::Halt),
+            _ => None,
+        }
+    }
+
+    pub fn opcode(&self) -> u32 {
+        match self {
+            Op::Add => ADD_OP,
+            Op::Mul => MUL_OP,
+            Op::Input => IN_OP,
+            Op::Output => OUT_OP,
+            Op::JmpIfTrue => JIFT_OP,
+            Op::JmpIfFalse => JIFF_OP,
+            Op::LessThan => LT_OP,
+            Op::Equals => EQ_OP,
+            Op::AdjustRelBase => ARB_OP,
+            Op::Halt => HALT_OP,
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Op::Add => "ADD",
+            Op::Mul => "MUL",
+            Op::Input => "IN",
+            Op::Output => "OUT",
+            Op::JmpIfTrue => "JT",
+            Op::JmpIfFalse => "JF",
+            Op::LessThan => "LT",
+            Op::Equals => "EQ",
+            Op::AdjustRelBase => "ARB",
+            Op::Halt => "HLT",
+        }
+    }
+
+    pub fn from_mnemonic(name: &str) -> Option<Op> {
+        ALL_OPS.iter().copied().find(|op| op.mnemonic().eq_ignore_ascii_case(name))
+    }
+
+    pub fn n_params(&self) -> usize {
+        match self {
+            Op::Add | Op::Mul | Op::LessThan | Op::Equals => 3,
+            Op::JmpIfTrue | Op::JmpIfFalse => 2,
+            Op::Input | Op::Output | Op::AdjustRelBase => 1,
+            Op::Halt => 0,
+        }
+    }
+
+    /// Index of the parameter the instruction writes to, if any.
+    pub fn out_param(&self) -> Option<usize> {
+        match self {
+            Op::Add | Op::Mul | Op::LessThan | Op::Equals => Some(2),
+            Op::Input => Some(0),
+            _ => None,
+        }
+    }
+}
+
+pub const ALL_OPS: [Op; 10] = [
+    Op::Add,
+    Op::Mul,
+    Op::Input,
+    Op::Output,
+    Op::JmpIfTrue,
+    Op::JmpIfFalse,
+    Op::LessThan,
+    Op::Equals,
+    Op::AdjustRelBase,
+    Op::Halt,
+];
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ParamMode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+impl ParamMode {
+    pub fn from_value(mode: u32) -> Option<ParamMode> {
+        match mode {
+            0 => Some(ParamMode::Position),
+            1 => Some(ParamMode::Immediate),
+            2 => Some(ParamMode::Relative),
+            _ => None,
+        }
+    }
+
+    pub fn value(&self) -> u32 {
+        match self {
+            ParamMode::Position => 0,
+            ParamMode::Immediate => 1,
+            ParamMode::Relative => 2,
+        }
+    }
+
+    /// Renders a raw parameter the way the disassembler prints it:
+    /// `[x]`, `#x` or `rb+x`.
+    pub fn format<W: Word>(&self, param: &W) -> String {
+        match self {
+            ParamMode::Position => format!("[{}]", param),
+            ParamMode::Immediate => format!("#{}", param),
+            ParamMode::Relative if *param < W::default() => format!("rb{}", param),
+            ParamMode::Relative => format!("rb+{}", param),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum DecodeError {
+    UnknownOpcode,
+    UnknownParamMode,
+}
+
+/// Splits an instruction word into its opcode and parameter modes.
+pub fn unpack_instr<W: Word>(instr: &W) -> Result<([ParamMode; 3], Op), DecodeError> {
+    let val = match instr.to_i128() {
+        Some(val) if (0..=u32::MAX as i128).contains(&val) => val as u32,
+        _ => return Err(DecodeError::UnknownOpcode),
+    };
+    let op = Op::from_value(val % 100).ok_or(DecodeError::UnknownOpcode)?;
+    let mode_a = ParamMode::from_value(val / 100 % 10).ok_or(DecodeError::UnknownParamMode)?;
+    let mode_b = ParamMode::from_value(val / 1000 % 10).ok_or(DecodeError::UnknownParamMode)?;
+    let mode_c = ParamMode::from_value(val / 10000).ok_or(DecodeError::UnknownParamMode)?;
+    Ok(([mode_a, mode_b, mode_c], op))
+}
+
+/// An instruction as the interpreter caches it: its op, the modes of its
+/// parameters and their raw words (default for parameters it does not take).
+#[derive(Clone, Debug)]
+pub(crate) struct Decoded<W> {
+    pub op: Op,
+    pub modes: [ParamMode; 3],
+    pub params: [W; 3],
+}
+
+/// Encodes an instruction word. Modes of parameters the op does not take
+/// are ignored.
+pub fn pack_instr(op: Op, modes: &[ParamMode]) -> i64 {
+    let mut word = op.opcode() as i64;
+    let mut scale = 100;
+    for mode in modes.iter().take(op.n_params()) {
+        word += mode.value() as i64 * scale;
+        scale *= 10;
+    }
+    word
+}
@@ -0,0 +1,416 @@
+//! Peephole optimization of program images.
+//!
+//! `optimize` rewrites a program into one that produces the same outputs
+//! for the same inputs and halts the same way; only memory the program
+//! never looks at may end up different. It makes four kinds of changes:
+//!
+//! - arithmetic on two immediates is folded into `ADD #result, #0, dest`,
+//! - a store that is overwritten further down the same straight run of
+//!   code, with nothing reading the cell in between, becomes a jump over
+//!   the instruction,
+//! - jumps to unconditional jumps go straight to where the chain ends,
+//! - words that are neither reachable code nor read as data are zeroed,
+//!   and trailing zeros dropped.
+//!
+//! Every change needs the analysis to know all the code that can run and
+//! the memory it can touch. Programs may change the immediate operands and
+//! addresses of their own instructions, but programs that change anything
+//! else in their code, jump through memory or use relative-mode operands
+//! are left as they are, unless they are compiled; see `Options::compiled`.
+//!
+//! That leaves the puzzle inputs alone with the default options: d09 and
+//! d11 use relative-mode operands, and d05 patches its first instruction
+//! with its input, so it is never rewritten. Compiled, d09 and d11 only
+//! get their arithmetic folded, and no memory is zeroed as long as the
+//! program reads memory through addresses it computes.
+
+use crate::disasm::{self, Item};
+use crate::op::{self, Op, ParamMode};
+
+// instructions a dead store search looks ahead
+const WINDOW: usize = 16;
+// jumps followed through a chain
+const MAX_CHAIN: usize = 16;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct Options {
+    /// Assume the program was generated by a compiler that keeps its stack
+    /// past the end of the image: relative-mode operands never address the
+    /// image, and jumps through memory only go to addresses the program
+    /// stores as constants, such as pushed return addresses.
+    pub compiled: bool,
+}
+
+/// How many changes of each kind were made.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct Report {
+    pub folded: usize,
+    pub dead_stores: usize,
+    pub threaded: usize,
+    pub zeroed: usize,
+}
+
+#[derive(Clone)]
+struct Instr {
+    addr: usize,
+    op: Op,
+    modes: [ParamMode; 3],
+    params: Vec<i128>,
+}
+
+impl Instr {
+    fn next(&self) -> usize {
+        self.addr + self.params.len() + 1
+    }
+
+    fn words(&self) -> std::ops::Range<usize> {
+        self.addr..self.next()
+    }
+
+    fn is_jump(&self) -> bool {
+        matches!(self.op, Op::JmpIfTrue | Op::JmpIfFalse)
+    }
+
+    /// Target of an unconditional jump with an immediate target.
+    fn always_jumps_to(&self) -> Option<usize> {
+        let taken = self.is_jump()
+            && self.modes[0] == ParamMode::Immediate
+            && (self.params[0] != 0) == (self.op == Op::JmpIfTrue);
+        match (taken, self.modes[1]) {
+            (true, ParamMode::Immediate) => usize::try_from(self.params[1]).ok(),
+            _ => None,
+        }
+    }
+
+    /// Where the machine can go next, given the cells of the image that
+    /// may be written, and whether it can also jump through memory.
+    fn successors(&self, program: &[i128], written: &[bool]) -> (Vec<usize>, bool) {
+        let next = self.next();
+        if self.op == Op::Halt {
+            return (vec![], false);
+        } else if !self.is_jump() {
+            return (vec![next], false);
+        }
+        let condition = match self.modes[0] {
+            ParamMode::Immediate => Some(self.params[0]),
+            ParamMode::Position => usize::try_from(self.params[0]).ok()
+                .filter(|&addr| addr < program.len() && !written[addr])
+                .map(|addr| program[addr]),
+            ParamMode::Relative => None,
+        };
+        let target = match self.modes[1] {
+            ParamMode::Immediate => usize::try_from(self.params[1]).ok(),
+            _ => None,
+        };
+        let indirect = self.modes[1] != ParamMode::Immediate;
+        match condition.map(|x| (x != 0) == (self.op == Op::JmpIfTrue)) {
+            Some(true) => (target.into_iter().collect(), indirect),
+            Some(false) => (vec![next], false),
+            None => (std::iter::once(next).chain(target).collect(), indirect),
+        }
+    }
+
+    /// Parameters read as values, i.e. all but the one written to.
+    fn reads(&self) -> impl Iterator<Item = (ParamMode, i128)> + '_ {
+        let out = self.op.out_param();
+        (0..self.params.len())
+            .filter(move |&n| Some(n) != out)
+            .map(move |n| (self.modes[n], self.params[n]))
+    }
+}
+
+// what the analysis found out about the whole program
+struct Facts {
+    code: Vec<Option<Instr>>,
+    // word belongs to a reachable instruction
+    in_code: Vec<bool>,
+    read: Vec<bool>,
+    written: Vec<bool>,
+    // every word may be read, or every word that is not code
+    read_all: bool,
+    read_data: bool,
+}
+
+impl Facts {
+    /// `None` if the code that can run is not known.
+    ///
+    /// Jumps conditioned on cells nothing writes are resolved, which can
+    /// only be done once the writes are known. Starting from no writes,
+    /// the cells that may be written grow until the code found writes no
+    /// others.
+    fn new(program: &[i128], options: Options) -> Option<Facts> {
+        let mut written = vec![false; program.len()];
+        loop {
+            let mut facts = Facts::scan(program, &written, options)?;
+            if facts.written.iter().zip(&written).all(|(&new, &old)| old || !new) {
+                facts.written = written;
+                return Some(facts);
+            }
+            for (old, new) in written.iter_mut().zip(&facts.written) {
+                *old |= new;
+            }
+        }
+    }
+
+    // the code reachable if only `written` cells of the image change
+    fn scan(program: &[i128], written: &[bool], options: Options) -> Option<Facts> {
+        let n = program.len();
+        let mut facts = Facts {
+            code: vec![None; n],
+            in_code: vec![false; n],
+            read: vec![false; n],
+            written: vec![false; n],
+            read_all: false,
+            read_data: false,
+        };
+        let indirect = facts.follow(program, written, 0)?;
+        if indirect && !options.compiled {
+            return None;
+        }
+        // compiled code only jumps through memory to constants it stores,
+        // but most constants are not addresses
+        let mut tried = vec![false; n];
+        while let Some(entry) = facts.code.iter().flatten()
+            .filter_map(|instr| usize::try_from(constant(instr)?).ok())
+            .find(|&x| x < n && !tried[x] && !facts.in_code[x])
+        {
+            tried[entry] = true;
+            facts.follow(program, written, entry);
+        }
+
+        // cells used by operands the program changes, or relative to the
+        // base, are only known to be somewhere
+        let mut writes_data = false;
+        for instr in facts.code.iter().flatten() {
+            let out = instr.op.out_param();
+            for (n, (&mode, &param)) in instr.modes.iter().zip(&instr.params).enumerate() {
+                let computed = mode == ParamMode::Relative || written[instr.addr + 1 + n];
+                let (anywhere, data) = match mode {
+                    ParamMode::Immediate => continue,
+                    ParamMode::Position if !computed => {
+                        mark(if Some(n) == out { &mut facts.written } else { &mut facts.read }, param);
+                        continue;
+                    }
+                    ParamMode::Relative if options.compiled => continue,
+                    ParamMode::Position if options.compiled => (false, true),
+                    _ => (true, false),
+                };
+                if Some(n) == out {
+                    if anywhere {
+                        return None;
+                    }
+                    writes_data |= data;
+                } else {
+                    facts.read_all |= anywhere;
+                    facts.read_data |= data;
+                }
+            }
+        }
+        if writes_data {
+            for (cell, &code) in facts.written.iter_mut().zip(&facts.in_code) {
+                *cell |= !code;
+            }
+        }
+        // code may change immediate operands and the addresses it computes
+        // with, but changing anything else could make it do anything
+        for instr in facts.code.iter().flatten() {
+            for (n, word) in instr.words().enumerate() {
+                if facts.written[word] && (n == 0 || instr.is_jump()) {
+                    return None;
+                }
+            }
+        }
+        Some(facts)
+    }
+
+    // adds the code reachable from `entry`, or nothing if some of it does
+    // not decode or overlaps code found before; true if it jumps through
+    // memory
+    fn follow(&mut self, program: &[i128], written: &[bool], entry: usize) -> Option<bool> {
+        let mut found: Vec<Instr> = Vec::new();
+        let mut taken = self.in_code.clone();
+        let mut todo = vec![entry];
+        let mut indirect = false;
+        while let Some(addr) = todo.pop() {
+            if self.instr(addr).is_some() || found.iter().any(|instr| instr.addr == addr) {
+                continue;
+            }
+            // the machine would execute whatever is there
+            let instr = match disasm::decode_at(program, addr)? {
+                Item::Instr { addr, op, modes, params } => Instr { addr, op, modes, params },
+                Item::Data { .. } => return None,
+            };
+            for word in instr.words() {
+                if std::mem::replace(&mut taken[word], true) {
+                    return None;
+                }
+            }
+            let (successors, through_memory) = instr.successors(program, written);
+            todo.extend(successors.into_iter().filter(|&s| s < program.len()));
+            indirect |= through_memory;
+            found.push(instr);
+        }
+        self.in_code = taken;
+        for instr in found {
+            let addr = instr.addr;
+            self.code[addr] = Some(instr);
+        }
+        Some(indirect)
+    }
+
+    // none of its words change while running
+    fn fixed(&self, instr: &Instr) -> bool {
+        !instr.words().any(|w| self.written[w])
+    }
+
+    fn read(&self, addr: usize) -> bool {
+        match (self.read.get(addr), self.in_code.get(addr)) {
+            (Some(&read), Some(&code)) => self.read_all || read || (self.read_data && !code),
+            _ => self.read_all || self.read_data,
+        }
+    }
+
+    fn instr(&self, addr: usize) -> Option<&Instr> {
+        self.code.get(addr)?.as_ref()
+    }
+}
+
+fn mark(cells: &mut [bool], addr: i128) {
+    if let Some(cell) = usize::try_from(addr).ok().and_then(|a| cells.get_mut(a)) {
+        *cell = true;
+    }
+}
+
+// the value stored by arithmetic on two immediates
+fn constant(instr: &Instr) -> Option<i128> {
+    let (a, b) = (instr.params.first()?, instr.params.get(1)?);
+    if instr.modes[0] != ParamMode::Immediate || instr.modes[1] != ParamMode::Immediate {
+        return None;
+    }
+    match instr.op {
+        Op::Add => a.checked_add(*b),
+        Op::Mul => a.checked_mul(*b),
+        Op::LessThan => Some(i128::from(a < b)),
+        Op::Equals => Some(i128::from(a == b)),
+        _ => None,
+    }
+}
+
+// the constant to store instead, if it is not stored like that already and
+// storing it cannot fail, which would show the rewritten instruction
+fn fold(instr: &Instr) -> Option<i128> {
+    let folded = instr.op == Op::Add && instr.params[1] == 0;
+    let stores = instr.modes[2] == ParamMode::Position && instr.params.get(2).is_some_and(|&addr| addr >= 0);
+    constant(instr).filter(|_| !folded && stores)
+}
+
+// whether running the instruction can fail, other than by writing
+fn can_fail(instr: &Instr) -> bool {
+    let bad_read = instr.reads().any(|(mode, param)| mode == ParamMode::Position && param < 0);
+    let (a, b) = (instr.params[0], instr.params[1]);
+    let immediates = instr.modes[0] == ParamMode::Immediate && instr.modes[1] == ParamMode::Immediate;
+    bad_read || match instr.op {
+        Op::Add => !immediates || a.checked_add(b).is_none(),
+        Op::Mul => !immediates || a.checked_mul(b).is_none(),
+        _ => false,
+    }
+}
+
+/// Whether the cell `store` writes is overwritten before anything can read
+/// it, looking down the straight run of code after it.
+fn dead_store(facts: &Facts, len: usize, store: &Instr) -> bool {
+    if !matches!(store.op, Op::Add | Op::Mul | Op::LessThan | Op::Equals) || can_fail(store) {
+        return false;
+    }
+    let target = match (store.modes[2], usize::try_from(store.params[2])) {
+        (ParamMode::Position, Ok(target)) if target < len => target as i128,
+        _ => return false,
+    };
+    let hits = |mode: ParamMode, param: i128| match mode {
+        ParamMode::Position => param == target,
+        ParamMode::Relative => facts.read_all,
+        ParamMode::Immediate => false,
+    };
+    let mut addr = store.next();
+    for _ in 0..WINDOW {
+        let instr = match facts.instr(addr) {
+            Some(instr) => instr,
+            None => return false,
+        };
+        if !facts.fixed(instr) || instr.is_jump() || instr.op == Op::Halt {
+            return false;
+        }
+        if instr.reads().any(|(mode, param)| hits(mode, param)) {
+            return false;
+        }
+        if let Some(out) = instr.op.out_param() {
+            if instr.modes[out] == ParamMode::Position && instr.params[out] == target {
+                return true;
+            }
+        }
+        addr = instr.next();
+    }
+    false
+}
+
+/// Where a chain of unconditional jumps starting at `target` ends.
+fn chain_end(facts: &Facts, mut target: usize) -> usize {
+    for _ in 0..MAX_CHAIN {
+        match facts.instr(target).and_then(Instr::always_jumps_to) {
+            Some(next) if next != target => target = next,
+            _ => break,
+        }
+    }
+    target
+}
+
+/// An equivalent program and what was changed to get it.
+pub fn optimize(program: &[i128], options: Options) -> (Vec<i128>, Report) {
+    let mut report = Report::default();
+    let facts = match Facts::new(program, options) {
+        Some(facts) => facts,
+        None => return (program.to_vec(), report),
+    };
+    let mut out = program.to_vec();
+    // rewritten words must not be read as data
+    let free = |words: std::ops::Range<usize>| !words.clone().any(|w| facts.read(w));
+
+    for instr in facts.code.iter().flatten() {
+        if !facts.fixed(instr) || !free(instr.words()) {
+            continue;
+        }
+        if dead_store(&facts, program.len(), instr) {
+            let jump = op::pack_instr(Op::JmpIfTrue, &[ParamMode::Immediate; 2]) as i128;
+            out[instr.addr..instr.next()].copy_from_slice(&[jump, 1, instr.next() as i128, 0]);
+            report.dead_stores += 1;
+        } else if let Some(x) = fold(instr) {
+            let modes = [ParamMode::Immediate, ParamMode::Immediate, instr.modes[2]];
+            out[instr.addr] = op::pack_instr(Op::Add, &modes) as i128;
+            out[instr.addr + 1] = x;
+            out[instr.addr + 2] = 0;
+            report.folded += 1;
+        } else if instr.is_jump() && instr.modes[1] == ParamMode::Immediate {
+            if let Ok(target) = usize::try_from(instr.params[1]) {
+                let end = chain_end(&facts, target);
+                if end != target {
+                    out[instr.addr + 2] = end as i128;
+                    report.threaded += 1;
+                }
+            }
+        }
+    }
+
+    // code the changes above cut off is unreachable too
+    if let Some(after) = Facts::new(&out, options) {
+        for (addr, word) in out.iter_mut().enumerate() {
+            if !after.in_code[addr] && !facts.read(addr) && *word != 0 {
+                *word = 0;
+                report.zeroed += 1;
+            }
+        }
+    }
+    while out.last() == Some(&0) {
+        out.pop();
+    }
+    (out, report)
+}
@@ -0,0 +1,200 @@
+//! Networks of machines exchanging packets.
+//!
+//! Every machine of a `Router` is booted with its address as its first
+//! input. After that it sends a packet by outputting a destination address
+//! followed by two values, X and Y, and receives packets as X then Y, or
+//! -1 when nothing is waiting for it. Packets to an address without a
+//! machine go to a `Monitor`, which also decides what happens once the
+//! network goes idle.
+
+use std::collections::VecDeque;
+
+use crate::cpu::{Cpu, State};
+use crate::limits::{Guard, Limits};
+use crate::threads::NodeError;
+use crate::word::Word;
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Packet<W = i128> {
+    /// Address of the machine that sent it.
+    pub from: usize,
+    pub dest: W,
+    pub x: W,
+    pub y: W,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Flow {
+    Continue,
+    Stop,
+}
+
+/// Watches a network from the outside.
+pub trait Monitor<W> {
+    /// A packet was sent to an address without a machine.
+    fn unrouted(&mut self, _packet: &Packet<W>) -> Flow {
+        Flow::Continue
+    }
+
+    /// The network went idle. Packets sent through `router` wake it up
+    /// again.
+    fn idle(&mut self, _router: &mut Router<W>) -> Flow {
+        Flow::Stop
+    }
+}
+
+/// Drops unrouted packets and stops once the network is idle.
+impl<W> Monitor<W> for () {}
+
+impl<W, M: Monitor<W> + ?Sized> Monitor<W> for &mut M {
+    fn unrouted(&mut self, packet: &Packet<W>) -> Flow {
+        (**self).unrouted(packet)
+    }
+
+    fn idle(&mut self, router: &mut Router<W>) -> Flow {
+        (**self).idle(router)
+    }
+}
+
+/// Why a network run ended.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Stop {
+    /// The monitor asked to stop after an unrouted packet.
+    Monitor,
+    /// The network was idle and the monitor did not wake it up.
+    Idle,
+    /// Every machine halted.
+    Halted,
+}
+
+// how a round went
+enum Round {
+    Busy,
+    Quiet,
+    Stopped,
+}
+
+/// Machines and the packets queued for them. Machines take turns in
+/// address order; in its turn a machine receives everything queued for it
+/// and runs until it needs more input.
+pub struct Router<W = i128> {
+    pub machines: Vec<Cpu<W>>,
+    queues: Vec<VecDeque<(W, W)>>,
+    halted: Vec<bool>,
+    guards: Vec<Guard<W>>,
+    /// Steps each machine may take in all, so one spinning forever cannot
+    /// hang the network. Unlimited by default.
+    pub max_steps: Option<u64>,
+    /// Rounds without any packet sent or received before the network
+    /// counts as idle. Defaults to 2, as a machine may read -1 once before
+    /// deciding to send something.
+    pub idle_rounds: usize,
+    /// Rounds run so far.
+    pub rounds: usize,
+}
+
+impl<W: Word> Router<W> {
+    /// Boots `machines`, giving each its index as its address.
+    pub fn new(mut machines: Vec<Cpu<W>>) -> Router<W> {
+        for (addr, cpu) in machines.iter_mut().enumerate() {
+            cpu.add_input(W::from_i64(addr as i64));
+        }
+        let n = machines.len();
+        Router {
+            machines,
+            queues: vec![VecDeque::new(); n],
+            halted: vec![false; n],
+            guards: vec![Guard::new(Limits::default()); n],
+            max_steps: None,
+            idle_rounds: 2,
+            rounds: 0,
+        }
+    }
+
+    /// `n` machines running `program`.
+    pub fn with_program(program: &[W], n: usize) -> Router<W> {
+        Router::new(vec![Cpu::new(program); n])
+    }
+
+    /// Queues a packet for machine `addr`.
+    pub fn send(&mut self, addr: usize, x: W, y: W) {
+        self.queues[addr].push_back((x, y));
+    }
+
+    /// Number of packets waiting for machine `addr`.
+    pub fn queued(&self, addr: usize) -> usize {
+        self.queues[addr].len()
+    }
+
+    // the machine a packet is for, if there is one
+    fn route(&self, dest: &W) -> Option<usize> {
+        let addr = usize::try_from(dest.to_i128()?).ok()?;
+        (addr < self.machines.len()).then_some(addr)
+    }
+
+    /// Gives every machine a turn.
+    fn round<M: Monitor<W>>(&mut self, monitor: &mut M) -> Result<Round, NodeError<W>> {
+        let mut busy = false;
+        for id in 0..self.machines.len() {
+            if self.halted[id] {
+                continue;
+            }
+            let cpu = &mut self.machines[id];
+            if self.queues[id].is_empty() {
+                cpu.add_input(W::from_i64(-1));
+            } else {
+                busy = true;
+                for (x, y) in self.queues[id].drain(..) {
+                    cpu.add_input(x);
+                    cpu.add_input(y);
+                }
+            }
+            let guard = &mut self.guards[id];
+            guard.limits.steps = self.max_steps;
+            match guard.run(cpu) {
+                Ok(State::Halted) => self.halted[id] = true,
+                Ok(_) => (),
+                Err(error) => return Err(NodeError { node: id, error }),
+            }
+            // a partly written packet stays until the rest of it is output
+            let whole = cpu.outputs.len() / 3 * 3;
+            let words: Vec<W> = cpu.outputs.drain(..whole).collect();
+            for words in words.chunks(3) {
+                busy = true;
+                let packet = Packet { from: id, dest: words[0].clone(), x: words[1].clone(), y: words[2].clone() };
+                match self.route(&packet.dest) {
+                    Some(addr) => self.send(addr, packet.x, packet.y),
+                    None => {
+                        if monitor.unrouted(&packet) == Flow::Stop {
+                            return Ok(Round::Stopped);
+                        }
+                    }
+                }
+            }
+        }
+        self.rounds += 1;
+        Ok(if busy { Round::Busy } else { Round::Quiet })
+    }
+
+    /// Runs rounds until every machine has halted, or the monitor stops the
+    /// network or leaves it idle, or a machine fails or runs out of steps.
+    pub fn run<M: Monitor<W>>(&mut self, mut monitor: M) -> Result<Stop, NodeError<W>> {
+        let mut quiet = 0;
+        loop {
+            if self.halted.iter().all(|&h| h) {
+                return Ok(Stop::Halted);
+            }
+            match self.round(&mut monitor)? {
+                Round::Stopped => return Ok(Stop::Monitor),
+                Round::Busy => quiet = 0,
+                Round::Quiet => quiet += 1,
+            }
+            if quiet >= self.idle_rounds {
+                if monitor.idle(self) == Flow::Stop || self.queues.iter().all(|q| q.is_empty()) {
+                    return Ok(Stop::Idle);
+                }
+                quiet = 0;
+            }
+        }
+    }
+}
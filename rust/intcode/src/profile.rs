@@ -0,0 +1,115 @@
+//! Execution counts per address and per opcode, and which instructions of
+//! a program a run never reached.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::cpu::Observer;
+use crate::disasm::{self, Item};
+use crate::op::{Op, ALL_OPS};
+use crate::word::Word;
+
+/// Observer counting how often every instruction executes.
+#[derive(Clone, Debug, Default)]
+pub struct Profiler {
+    pub counts: BTreeMap<usize, u64>,
+    pub op_counts: BTreeMap<u32, u64>,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler::default()
+    }
+
+    pub fn count(&self, addr: usize) -> u64 {
+        self.counts.get(&addr).copied().unwrap_or(0)
+    }
+
+    pub fn op_count(&self, op: Op) -> u64 {
+        self.op_counts.get(&op.opcode()).copied().unwrap_or(0)
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    /// The addresses that executed most often, hottest first.
+    pub fn hottest(&self, n: usize) -> Vec<(usize, u64)> {
+        let mut hot: Vec<(usize, u64)> = self.counts.iter().map(|(&addr, &count)| (addr, count)).collect();
+        hot.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hot.truncate(n);
+        hot
+    }
+
+    /// The listing of `program` with code followed from every executed
+    /// address as well as from 0.
+    fn listing<W: Word>(&self, program: &[W]) -> Vec<Item<W>> {
+        let entries: Vec<usize> = std::iter::once(0).chain(self.counts.keys().copied()).collect();
+        disasm::disassemble_from(program, &entries)
+    }
+
+    /// Instructions of the listing that never executed.
+    pub fn unreached<W: Word>(&self, program: &[W]) -> Vec<usize> {
+        self.listing(program).iter()
+            .filter(|item| matches!(item, Item::Instr { .. }) && self.count(item.addr()) == 0)
+            .map(|item| item.addr())
+            .collect()
+    }
+
+    /// The disassembly of `program` with an execution count column, then
+    /// any executed addresses the listing cannot show as code (such as
+    /// self-modified instructions), a coverage summary and the opcode
+    /// histogram.
+    pub fn report<W: Word>(&self, program: &[W]) -> String {
+        let items = self.listing(program);
+        let mut out = String::new();
+        let mut n_instrs = 0;
+        let mut n_reached = 0;
+        for item in &items {
+            if let Item::Instr { addr, .. } = item {
+                let count = self.count(*addr);
+                n_instrs += 1;
+                if count > 0 {
+                    n_reached += 1;
+                    writeln!(out, "{:>10} {}", count, item).unwrap();
+                } else {
+                    writeln!(out, "{:>10} {}", "-", item).unwrap();
+                }
+            } else {
+                writeln!(out, "{:>10} {}", "", item).unwrap();
+            }
+        }
+
+        let listed: Vec<usize> = items.iter()
+            .filter(|item| matches!(item, Item::Instr { .. }))
+            .map(|item| item.addr())
+            .collect();
+        let outside: Vec<(&usize, &u64)> = self.counts.iter()
+            .filter(|(addr, _)| listed.binary_search(addr).is_err())
+            .collect();
+        if !outside.is_empty() {
+            writeln!(out, "\nexecuted outside the listing:").unwrap();
+            for (addr, count) in outside {
+                writeln!(out, "{:>10} {:>5}", count, addr).unwrap();
+            }
+        }
+
+        let percent = if n_instrs > 0 { 100.0 * n_reached as f64 / n_instrs as f64 } else { 0.0 };
+        writeln!(out, "\ncoverage: {} of {} instructions ({:.1}%), {} steps",
+                 n_reached, n_instrs, percent, self.total()).unwrap();
+        for op in ALL_OPS {
+            let count = self.op_count(op);
+            if count > 0 {
+                writeln!(out, "{:<4} {:>10}", op.mnemonic(), count).unwrap();
+            }
+        }
+        out
+    }
+}
+
+impl<W> Observer<W> for Profiler {
+    fn exec(&mut self, pc: usize, op: Op) {
+        *self.counts.entry(pc).or_insert(0) += 1;
+        *self.op_counts.entry(op.opcode()).or_insert(0) += 1;
+    }
+}
@@ -0,0 +1,239 @@
+//! Searching for program inputs, in parallel.
+//!
+//! A `Search` patches some memory cells and input slots of a machine with
+//! every combination of values from their ranges, runs it, and asks a
+//! predicate about the result. Combinations are numbered in order, first
+//! slot slowest, and handed out to worker threads in chunks; `find` still
+//! returns the first matching combination in that order, so the answer
+//! does not depend on the number of threads.
+
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::aot;
+use crate::cpu::{Cpu, State};
+use crate::error::Error;
+use crate::symbolic::Symbol;
+
+// combinations a worker claims at a time
+const CHUNK: u64 = 64;
+
+/// A machine a search can patch and run.
+pub trait Runnable: Clone + Send + Sync {
+    fn cpu(&mut self) -> &mut Cpu;
+    fn run_for(&mut self, max_steps: usize) -> Result<State, Error>;
+}
+
+impl Runnable for Cpu {
+    fn cpu(&mut self) -> &mut Cpu {
+        self
+    }
+
+    fn run_for(&mut self, max_steps: usize) -> Result<State, Error> {
+        Cpu::run_for(self, max_steps)
+    }
+}
+
+impl Runnable for aot::Machine {
+    fn cpu(&mut self) -> &mut Cpu {
+        self
+    }
+
+    fn run_for(&mut self, max_steps: usize) -> Result<State, Error> {
+        aot::Machine::run_for(self, max_steps)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Input {
+    Known(i128),
+    // index into the slots
+    Slot(usize),
+}
+
+fn len(values: &RangeInclusive<i128>) -> Option<u64> {
+    if values.is_empty() {
+        return Some(0);
+    }
+    u64::try_from(values.end().abs_diff(*values.start())).ok()?.checked_add(1)
+}
+
+type Prune<'a> = Box<dyn Fn(&[i128]) -> bool + Sync + 'a>;
+
+/// Runs of a machine over every combination of slot values.
+pub struct Search<'a, M> {
+    init: &'a M,
+    slots: Vec<(Symbol, RangeInclusive<i128>)>,
+    inputs: Vec<Input>,
+    prune: Option<Prune<'a>>,
+    /// Worker threads; defaults to the number of cores.
+    pub threads: usize,
+    /// Steps a single run may take. Runs that fail or hit the limit never
+    /// match.
+    pub max_steps: usize,
+}
+
+impl<'a, M: Runnable> Search<'a, M> {
+    pub fn new(init: &'a M) -> Search<'a, M> {
+        Search {
+            init,
+            slots: Vec::new(),
+            inputs: Vec::new(),
+            prune: None,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            max_steps: 1_000_000,
+        }
+    }
+
+    /// Patches the cell at `addr` with each value of `values`.
+    pub fn mem(&mut self, addr: usize, values: RangeInclusive<i128>) -> &mut Search<'a, M> {
+        self.slots.push((Symbol::Mem(addr), values));
+        self
+    }
+
+    /// Queues an input taking each value of `values`.
+    pub fn input(&mut self, values: RangeInclusive<i128>) -> &mut Search<'a, M> {
+        self.inputs.push(Input::Slot(self.slots.len()));
+        self.slots.push((Symbol::Input(self.inputs.len() - 1), values));
+        self
+    }
+
+    /// Queues an input with a fixed value.
+    pub fn known_input(&mut self, x: i128) -> &mut Search<'a, M> {
+        self.inputs.push(Input::Known(x));
+        self
+    }
+
+    /// Skips every combination starting with values for which `prune`
+    /// returns true. It is called with the values of the first slot, then
+    /// the first two, and so on.
+    pub fn prune(&mut self, prune: impl Fn(&[i128]) -> bool + Sync + 'a) -> &mut Search<'a, M> {
+        self.prune = Some(Box::new(prune));
+        self
+    }
+
+    /// Number of combinations, or `None` if there are more than a `u64`
+    /// can count. Such searches find nothing.
+    pub fn combinations(&self) -> Option<u64> {
+        self.strides().map(|(_, total)| total)
+    }
+
+    // number of combinations sharing a prefix of each length, and in all
+    fn strides(&self) -> Option<(Vec<u64>, u64)> {
+        let mut strides = vec![0; self.slots.len()];
+        let mut total: u64 = 1;
+        for (i, (_, values)) in self.slots.iter().enumerate().rev() {
+            strides[i] = total;
+            total = total.checked_mul(len(values)?)?;
+        }
+        Some((strides, total))
+    }
+
+    // only called with indices below the total, so every range has a length
+    fn values(&self, strides: &[u64], index: u64) -> Vec<i128> {
+        self.slots.iter().zip(strides)
+            .map(|((_, values), &stride)| values.start() + ((index / stride) % len(values).unwrap()) as i128)
+            .collect()
+    }
+
+    fn run(&self, values: &[i128]) -> Option<M> {
+        let mut machine = self.init.clone();
+        let cpu = machine.cpu();
+        for ((slot, _), &x) in self.slots.iter().zip(values) {
+            if let Symbol::Mem(addr) = *slot {
+                cpu.mem[addr] = x;
+            }
+        }
+        for input in &self.inputs {
+            cpu.add_input(match *input {
+                Input::Known(x) => x,
+                Input::Slot(i) => values[i],
+            });
+        }
+        match machine.run_for(self.max_steps) {
+            Ok(State::StepLimitReached) | Err(_) => None,
+            Ok(_) => Some(machine),
+        }
+    }
+
+    fn scan<P>(&self, pred: P, first_only: bool) -> Vec<(u64, Vec<i128>)>
+    where
+        P: Fn(&[i128]) -> bool + Sync,
+    {
+        let Some((strides, total)) = self.strides() else {
+            return Vec::new();
+        };
+        let next = AtomicU64::new(0);
+        // index of the first match found so far
+        let first = AtomicU64::new(u64::MAX);
+        let found = Mutex::new(Vec::new());
+        let worker = || loop {
+            let start = next.fetch_add(CHUNK, Ordering::Relaxed);
+            if start >= total || (first_only && first.load(Ordering::Relaxed) < start) {
+                return;
+            }
+            let end = total.min(start + CHUNK);
+            let mut index = start;
+            'candidates: while index < end {
+                let values = self.values(&strides, index);
+                if let Some(prune) = &self.prune {
+                    for len in 1..=values.len() {
+                        if prune(&values[..len]) {
+                            index = (index / strides[len - 1] + 1) * strides[len - 1];
+                            continue 'candidates;
+                        }
+                    }
+                }
+                if pred(&values) {
+                    found.lock().unwrap().push((index, values));
+                    if first_only {
+                        first.fetch_min(index, Ordering::Relaxed);
+                        break;
+                    }
+                }
+                index += 1;
+            }
+        };
+        thread::scope(|scope| {
+            for _ in 0..self.threads.max(1) {
+                scope.spawn(worker);
+            }
+        });
+        let mut found = found.into_inner().unwrap();
+        found.sort();
+        found
+    }
+
+    /// The first combination of slot values, in order, for which the
+    /// finished machine satisfies `pred`. Workers stop once no earlier
+    /// combination is left. Finds nothing if `combinations` is `None`.
+    pub fn find<P>(&self, pred: P) -> Option<Vec<i128>>
+    where
+        P: Fn(&M) -> bool + Sync,
+    {
+        self.find_values(|values| self.run(values).is_some_and(|machine| pred(&machine)))
+    }
+
+    /// Like `find`, but decides on the values alone, without running the
+    /// machine.
+    pub fn find_values<P>(&self, pred: P) -> Option<Vec<i128>>
+    where
+        P: Fn(&[i128]) -> bool + Sync,
+    {
+        self.scan(pred, true).into_iter().next().map(|(_, values)| values)
+    }
+
+    /// Every combination for which `pred` holds, in order. Empty if
+    /// `combinations` is `None`.
+    pub fn find_all<P>(&self, pred: P) -> Vec<Vec<i128>>
+    where
+        P: Fn(&M) -> bool + Sync,
+    {
+        self.scan(|values| self.run(values).is_some_and(|machine| pred(&machine)), false)
+            .into_iter()
+            .map(|(_, values)| values)
+            .collect()
+    }
+}
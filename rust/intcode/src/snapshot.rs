@@ -0,0 +1,124 @@
+//! Saving machine state to disk and loading it back.
+//!
+//! ```text
+//! intcode-state 1
+//! pc 2
+//! rb 0
+//! overflow check
+//! limit none
+//! inputs 5
+//! outputs
+//! extent 10
+//! mem 0 3,9,4,9,99
+//! ```
+//!
+//! Every allocated page is one `mem` line with its first address and its
+//! words up to the last nonzero one.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use crate::cpu::Cpu;
+use crate::word::{Overflow, Word};
+
+const HEADER: &str = "intcode-state 1";
+
+fn join<'a, W: Word>(words: impl Iterator<Item = &'a W>) -> String {
+    let words: Vec<String> = words.map(|x| x.to_string()).collect();
+    words.join(",")
+}
+
+fn bad_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn parse<T: std::str::FromStr>(text: &str, what: &str) -> io::Result<T> {
+    text.trim().parse().map_err(|_| bad_data(format!("bad {} `{}`", what, text)))
+}
+
+fn parse_words<W: Word>(text: &str) -> io::Result<Vec<W>> {
+    if text.trim().is_empty() {
+        Ok(Vec::new())
+    } else {
+        text.split(',').map(|x| parse(x, "word")).collect()
+    }
+}
+
+impl<W: Word> Cpu<W> {
+    /// Writes the full machine state in a form `Cpu::load_state` reads back.
+    pub fn save_state<T: Write>(&self, mut out: T) -> io::Result<()> {
+        writeln!(out, "{}", HEADER)?;
+        writeln!(out, "pc {}", self.pc)?;
+        writeln!(out, "rb {}", self.base_offset)?;
+        let overflow = match self.overflow() {
+            Overflow::Wrap => "wrap",
+            Overflow::Check => "check",
+        };
+        writeln!(out, "overflow {}", overflow)?;
+        match self.mem.limit() {
+            usize::MAX => writeln!(out, "limit none")?,
+            limit => writeln!(out, "limit {}", limit)?,
+        }
+        writeln!(out, "{}", format!("inputs {}", join(self.inputs.iter())).trim_end())?;
+        writeln!(out, "{}", format!("outputs {}", join(self.outputs.iter())).trim_end())?;
+        writeln!(out, "extent {}", self.mem.extent())?;
+        for (addr, page) in self.mem.pages() {
+            let len = page.iter().rposition(|x| !x.is_zero()).map_or(0, |i| i + 1);
+            if len > 0 {
+                writeln!(out, "mem {} {}", addr, join(page[..len].iter()))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn load_state<T: BufRead>(input: T) -> io::Result<Cpu<W>> {
+        let mut lines = input.lines();
+        match lines.next() {
+            Some(Ok(line)) if line.trim() == HEADER => (),
+            Some(Err(err)) => return Err(err),
+            _ => return Err(bad_data("not an intcode machine state".to_string())),
+        }
+        let mut fields = HashMap::new();
+        let mut pages = Vec::new();
+        for line in lines {
+            let line = line?;
+            let (key, value) = line.split_once(' ').unwrap_or((&line, ""));
+            if key == "mem" {
+                let (addr, words) = value.split_once(' ').unwrap_or((value, ""));
+                let (addr, words) = (parse::<usize>(addr, "address")?, parse_words::<W>(words)?);
+                if addr.checked_add(words.len()).is_none() {
+                    return Err(bad_data(format!("words at {} run past the end of memory", addr)));
+                }
+                pages.push((addr, words));
+            } else {
+                fields.insert(key.to_string(), value.to_string());
+            }
+        }
+        let field = |key: &str| {
+            fields.get(key).map(|value| value.as_str())
+                .ok_or_else(|| bad_data(format!("missing `{}`", key)))
+        };
+
+        let overflow = match field("overflow")? {
+            "wrap" => Overflow::Wrap,
+            "check" => Overflow::Check,
+            other => return Err(bad_data(format!("bad overflow mode `{}`", other))),
+        };
+        let mut cpu = Cpu::with_overflow(&[], overflow);
+        cpu.pc = parse(field("pc")?, "pc")?;
+        cpu.base_offset = parse(field("rb")?, "relative base")?;
+        cpu.inputs = parse_words(field("inputs")?)?.into();
+        cpu.outputs = parse_words(field("outputs")?)?;
+        for (start, words) in pages {
+            for (addr, x) in (start..).zip(words) {
+                cpu.mem[addr] = x;
+            }
+        }
+        cpu.mem.set_extent(parse(field("extent")?, "extent")?);
+        match field("limit")? {
+            "none" => (),
+            limit => cpu.mem.set_limit(parse(limit, "limit")?),
+        }
+        Ok(cpu)
+    }
+}
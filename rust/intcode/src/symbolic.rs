@@ -0,0 +1,574 @@
+//! Symbolic execution, for finding the inputs that make a program produce
+//! a given value.
+//!
+//! A `SymbolicCpu` runs a program with some memory cells or inputs
+//! replaced by symbols, tracking what every value is as an `Expr` over
+//! them. That only works as long as the symbols do not decide where the
+//! program goes: an instruction, write address or branch condition
+//! depending on a symbol stops it. Reading through an address that depends
+//! on a symbol is fine, but gives a value that is not known in advance.
+//!
+//! `Problem` builds on it to solve for a target value. A goal that turns
+//! out linear in the symbols is solved directly; anything else falls back
+//! to trying every combination of symbol values within their bounds.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::ops::{Add, Mul, RangeInclusive};
+
+use crate::cpu::{Cpu, State};
+use crate::error::Error;
+use crate::op::{self, DecodeError, Op, ParamMode};
+use crate::search::Search;
+
+/// An unknown value.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub enum Symbol {
+    /// The initial value of a memory cell.
+    Mem(usize),
+    /// The n-th input.
+    Input(usize),
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Symbol::Mem(addr) => write!(f, "[{}]", addr),
+            Symbol::Input(n) => write!(f, "in{}", n),
+        }
+    }
+}
+
+/// A value in terms of symbols. Build with `+`, `*` and the comparison
+/// functions, which fold constants.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Expr {
+    Const(i128),
+    Sym(Symbol),
+    Add(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    LessThan(Box<Expr>, Box<Expr>),
+    Equals(Box<Expr>, Box<Expr>),
+    /// Whatever was in memory at an address depending on symbols.
+    Load(Box<Expr>),
+}
+
+/// `constant + sum(coeff * symbol)`.
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub struct Linear {
+    pub constant: i128,
+    pub coeffs: BTreeMap<Symbol, i128>,
+}
+
+impl Linear {
+    fn add(mut self, other: Linear) -> Option<Linear> {
+        self.constant = self.constant.checked_add(other.constant)?;
+        for (sym, c) in other.coeffs {
+            let coeff = self.coeffs.entry(sym).or_insert(0);
+            *coeff = coeff.checked_add(c)?;
+        }
+        self.coeffs.retain(|_, c| *c != 0);
+        Some(self)
+    }
+
+    fn scale(mut self, k: i128) -> Option<Linear> {
+        self.constant = self.constant.checked_mul(k)?;
+        for c in self.coeffs.values_mut() {
+            *c = c.checked_mul(k)?;
+        }
+        self.coeffs.retain(|_, c| *c != 0);
+        Some(self)
+    }
+}
+
+impl Expr {
+    pub fn less_than(a: Expr, b: Expr) -> Expr {
+        match (a, b) {
+            (Expr::Const(x), Expr::Const(y)) => Expr::Const(i128::from(x < y)),
+            (a, b) => Expr::LessThan(Box::new(a), Box::new(b)),
+        }
+    }
+
+    pub fn equals(a: Expr, b: Expr) -> Expr {
+        match (a, b) {
+            (Expr::Const(x), Expr::Const(y)) => Expr::Const(i128::from(x == y)),
+            (a, b) if a == b => Expr::Const(1),
+            (a, b) => Expr::Equals(Box::new(a), Box::new(b)),
+        }
+    }
+
+    pub fn as_const(&self) -> Option<i128> {
+        match *self {
+            Expr::Const(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    /// Whether the expression contains a `Load`, whose value cannot be
+    /// worked out from the symbols alone.
+    pub fn has_load(&self) -> bool {
+        match self {
+            Expr::Const(_) | Expr::Sym(_) => false,
+            Expr::Add(a, b) | Expr::Mul(a, b) | Expr::LessThan(a, b) | Expr::Equals(a, b) => {
+                a.has_load() || b.has_load()
+            }
+            Expr::Load(_) => true,
+        }
+    }
+
+    /// The value of the expression for the given symbol values, `None` if
+    /// a symbol has no value, the arithmetic overflows or it has a `Load`.
+    pub fn eval(&self, values: &BTreeMap<Symbol, i128>) -> Option<i128> {
+        match self {
+            Expr::Const(x) => Some(*x),
+            Expr::Sym(sym) => values.get(sym).copied(),
+            Expr::Add(a, b) => a.eval(values)?.checked_add(b.eval(values)?),
+            Expr::Mul(a, b) => a.eval(values)?.checked_mul(b.eval(values)?),
+            Expr::LessThan(a, b) => Some(i128::from(a.eval(values)? < b.eval(values)?)),
+            Expr::Equals(a, b) => Some(i128::from(a.eval(values)? == b.eval(values)?)),
+            Expr::Load(_) => None,
+        }
+    }
+
+    /// The expression as a linear combination of its symbols, if it is one.
+    pub fn linear(&self) -> Option<Linear> {
+        match self {
+            Expr::Const(x) => Some(Linear { constant: *x, coeffs: BTreeMap::new() }),
+            Expr::Sym(sym) => Some(Linear { constant: 0, coeffs: [(*sym, 1)].into() }),
+            Expr::Add(a, b) => a.linear()?.add(b.linear()?),
+            Expr::Mul(a, b) => {
+                let (a, b) = (a.linear()?, b.linear()?);
+                match (a.coeffs.is_empty(), b.coeffs.is_empty()) {
+                    (true, _) => b.scale(a.constant),
+                    (_, true) => a.scale(b.constant),
+                    _ => None,
+                }
+            }
+            Expr::LessThan(..) | Expr::Equals(..) | Expr::Load(_) => None,
+        }
+    }
+}
+
+impl Add for Expr {
+    type Output = Expr;
+
+    fn add(self, other: Expr) -> Expr {
+        match (self, other) {
+            (Expr::Const(x), Expr::Const(y)) if x.checked_add(y).is_some() => Expr::Const(x + y),
+            (Expr::Const(0), e) | (e, Expr::Const(0)) => e,
+            (a, b) => Expr::Add(Box::new(a), Box::new(b)),
+        }
+    }
+}
+
+impl Mul for Expr {
+    type Output = Expr;
+
+    fn mul(self, other: Expr) -> Expr {
+        match (self, other) {
+            (Expr::Const(x), Expr::Const(y)) if x.checked_mul(y).is_some() => Expr::Const(x * y),
+            (Expr::Const(0), _) | (_, Expr::Const(0)) => Expr::Const(0),
+            (Expr::Const(1), e) | (e, Expr::Const(1)) => e,
+            (a, b) => Expr::Mul(Box::new(a), Box::new(b)),
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Const(x) => write!(f, "{}", x),
+            Expr::Sym(sym) => write!(f, "{}", sym),
+            Expr::Add(a, b) => write!(f, "({} + {})", a, b),
+            Expr::Mul(a, b) => write!(f, "{} * {}", a, b),
+            Expr::LessThan(a, b) => write!(f, "({} < {})", a, b),
+            Expr::Equals(a, b) => write!(f, "({} == {})", a, b),
+            Expr::Load(addr) => write!(f, "load({})", addr),
+        }
+    }
+}
+
+/// Why symbolic execution stopped.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum SymError {
+    /// The instruction at pc depends on a symbol.
+    SymbolicInstr { pc: usize },
+    /// A write address, jump target or relative base offset depends on a
+    /// symbol.
+    SymbolicAddress { pc: usize },
+    /// Whether a jump is taken depends on a symbol.
+    SymbolicBranch { pc: usize },
+    /// The program fails whatever the symbols are.
+    Machine(Error),
+    /// The program was still running after the step budget, at pc.
+    StepLimitReached { pc: usize },
+}
+
+impl fmt::Display for SymError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SymError::SymbolicInstr { pc } => write!(f, "symbolic instruction at pc {}", pc),
+            SymError::SymbolicAddress { pc } => write!(f, "symbolic address at pc {}", pc),
+            SymError::SymbolicBranch { pc } => write!(f, "branch on a symbolic value at pc {}", pc),
+            SymError::Machine(err) => write!(f, "{}", err),
+            SymError::StepLimitReached { pc } => write!(f, "still running at pc {} after the step budget", pc),
+        }
+    }
+}
+
+impl std::error::Error for SymError {}
+
+impl From<Error> for SymError {
+    fn from(err: Error) -> SymError {
+        SymError::Machine(err)
+    }
+}
+
+/// A `Cpu` whose memory, inputs and outputs are expressions.
+#[derive(Clone, Debug)]
+pub struct SymbolicCpu {
+    pub pc: usize,
+    pub base_offset: i64,
+    mem: HashMap<usize, Expr>,
+    pub inputs: VecDeque<Expr>,
+    pub outputs: Vec<Expr>,
+}
+
+impl SymbolicCpu {
+    pub fn new(program: &[i128]) -> SymbolicCpu {
+        SymbolicCpu {
+            pc: 0,
+            base_offset: 0,
+            mem: program.iter().enumerate().map(|(addr, &x)| (addr, Expr::Const(x))).collect(),
+            inputs: VecDeque::new(),
+            outputs: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, addr: usize) -> Expr {
+        self.mem.get(&addr).cloned().unwrap_or(Expr::Const(0))
+    }
+
+    pub fn set(&mut self, addr: usize, x: Expr) {
+        self.mem.insert(addr, x);
+    }
+
+    /// Replaces the cell at `addr` with the symbol `Symbol::Mem(addr)`.
+    pub fn make_symbolic(&mut self, addr: usize) {
+        self.set(addr, Expr::Sym(Symbol::Mem(addr)));
+    }
+
+    fn instr(&self) -> i128 {
+        self.get(self.pc).as_const().unwrap_or_default()
+    }
+
+    fn concrete(&self, x: &Expr) -> Result<i128, SymError> {
+        x.as_const().ok_or(SymError::SymbolicAddress { pc: self.pc })
+    }
+
+    fn address(&self, addr: i128) -> Result<usize, SymError> {
+        usize::try_from(addr).map_err(|_| {
+            SymError::Machine(Error::NegativeAddress { pc: self.pc, instr: self.instr(), addr })
+        })
+    }
+
+    fn location(&self, mode: ParamMode, param: &Expr) -> Result<usize, SymError> {
+        let param = self.concrete(param)?;
+        match mode {
+            ParamMode::Position => self.address(param),
+            ParamMode::Immediate => Err(Error::ImmediateWrite { pc: self.pc, instr: self.instr() }.into()),
+            ParamMode::Relative => {
+                let addr = param.checked_add(self.base_offset.into())
+                    .ok_or(Error::Overflow { pc: self.pc, instr: self.instr() })?;
+                self.address(addr)
+            }
+        }
+    }
+
+    fn operand(&self, mode: ParamMode, param: &Expr) -> Result<Expr, SymError> {
+        match mode {
+            ParamMode::Immediate => Ok(param.clone()),
+            _ if param.as_const().is_none() => Ok(Expr::Load(Box::new(match mode {
+                ParamMode::Relative => Expr::Const(self.base_offset.into()) + param.clone(),
+                _ => param.clone(),
+            }))),
+            _ => Ok(self.get(self.location(mode, param)?)),
+        }
+    }
+
+    pub fn step(&mut self) -> Result<State<Expr>, SymError> {
+        let pc = self.pc;
+        let instr = self.get(pc).as_const().ok_or(SymError::SymbolicInstr { pc })?;
+        let (modes, op) = op::unpack_instr(&instr).map_err(|err| match err {
+            DecodeError::UnknownOpcode => Error::UnknownOpcode { pc, instr },
+            DecodeError::UnknownParamMode => Error::UnknownParamMode { pc, instr },
+        })?;
+        let params: Vec<Expr> = (1..=op.n_params()).map(|n| self.get(pc + n)).collect();
+        let arg = |n: usize| self.operand(modes[n], &params[n]);
+        let next = pc + op.n_params() + 1;
+        let result = match op {
+            Op::Add => Some(arg(0)? + arg(1)?),
+            Op::Mul => Some(arg(0)? * arg(1)?),
+            Op::LessThan => Some(Expr::less_than(arg(0)?, arg(1)?)),
+            Op::Equals => Some(Expr::equals(arg(0)?, arg(1)?)),
+            Op::Input => match self.inputs.pop_front() {
+                Some(x) => Some(x),
+                None => return Ok(State::NeedsInput),
+            },
+            Op::Output => {
+                let x = arg(0)?;
+                self.pc = next;
+                return Ok(State::Output(x));
+            }
+            Op::JmpIfTrue | Op::JmpIfFalse => {
+                let cond = arg(0)?.as_const().ok_or(SymError::SymbolicBranch { pc })?;
+                if (cond != 0) == (op == Op::JmpIfTrue) {
+                    let target = self.concrete(&arg(1)?)?;
+                    self.pc = self.address(target)?;
+                } else {
+                    self.pc = next;
+                }
+                return Ok(State::Running);
+            }
+            Op::AdjustRelBase => {
+                let a = self.concrete(&arg(0)?)?;
+                self.base_offset = i64::try_from(a).ok()
+                    .and_then(|a| self.base_offset.checked_add(a))
+                    .ok_or(Error::Overflow { pc, instr })?;
+                None
+            }
+            Op::Halt => return Ok(State::Halted),
+        };
+        if let (Some(x), Some(n)) = (result, op.out_param()) {
+            let addr = self.location(modes[n], &params[n])?;
+            self.set(addr, x);
+        }
+        self.pc = next;
+        Ok(State::Running)
+    }
+
+    /// Runs until the program halts or needs input, for at most `max_steps`
+    /// steps.
+    pub fn run_for(&mut self, max_steps: usize) -> Result<State<Expr>, SymError> {
+        for _ in 0..max_steps {
+            match self.step()? {
+                State::Running => continue,
+                State::Output(x) => self.outputs.push(x),
+                state => return Ok(state),
+            }
+        }
+        Ok(State::StepLimitReached)
+    }
+}
+
+/// The value a `Problem` solves for, read once the program stops.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Goal {
+    Mem(usize),
+    Output(usize),
+}
+
+/// How a solution was found.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Method {
+    /// Solved from the goal's linear expression.
+    Linear,
+    /// Search over the values of a non-linear goal expression.
+    Expression,
+    /// Search running the program for every candidate, as symbolic
+    /// execution could not finish.
+    Execution,
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Solution {
+    /// Values of the problem's symbols, in the order they were added.
+    pub values: Vec<i128>,
+    pub method: Method,
+}
+
+/// A program with some of its memory cells or inputs unknown, each within
+/// bounds.
+#[derive(Clone, Debug)]
+pub struct Problem<'a> {
+    program: &'a [i128],
+    symbols: Vec<(Symbol, RangeInclusive<i128>)>,
+    inputs: Vec<Expr>,
+    /// Steps a single run may take.
+    pub max_steps: usize,
+}
+
+impl<'a> Problem<'a> {
+    pub fn new(program: &'a [i128]) -> Problem<'a> {
+        Problem { program, symbols: Vec::new(), inputs: Vec::new(), max_steps: 1_000_000 }
+    }
+
+    /// Makes the initial value of the cell at `addr` a symbol.
+    pub fn mem(&mut self, addr: usize, bounds: RangeInclusive<i128>) -> &mut Problem<'a> {
+        self.symbols.push((Symbol::Mem(addr), bounds));
+        self
+    }
+
+    /// Queues a symbolic input.
+    pub fn input(&mut self, bounds: RangeInclusive<i128>) -> &mut Problem<'a> {
+        let sym = Symbol::Input(self.inputs.len());
+        self.inputs.push(Expr::Sym(sym));
+        self.symbols.push((sym, bounds));
+        self
+    }
+
+    /// Queues a known input.
+    pub fn known_input(&mut self, x: i128) -> &mut Problem<'a> {
+        self.inputs.push(Expr::Const(x));
+        self
+    }
+
+    /// The goal as an expression over the symbols, once the program has
+    /// stopped.
+    pub fn expr(&self, goal: Goal) -> Result<Expr, SymError> {
+        let mut cpu = SymbolicCpu::new(self.program);
+        for (sym, _) in &self.symbols {
+            if let Symbol::Mem(addr) = *sym {
+                cpu.make_symbolic(addr);
+            }
+        }
+        cpu.inputs.extend(self.inputs.iter().cloned());
+        if cpu.run_for(self.max_steps)? == State::StepLimitReached {
+            return Err(SymError::StepLimitReached { pc: cpu.pc });
+        }
+        Ok(match goal {
+            Goal::Mem(addr) => cpu.get(addr),
+            Goal::Output(n) => cpu.outputs.get(n).cloned().unwrap_or(Expr::Const(0)),
+        })
+    }
+
+    fn assignment(&self, values: &[i128]) -> BTreeMap<Symbol, i128> {
+        self.symbols.iter().map(|(sym, _)| *sym).zip(values.iter().copied()).collect()
+    }
+
+    /// The goal computed by actually running the program.
+    fn run(&self, goal: Goal, values: &[i128]) -> Option<i128> {
+        let assignment = self.assignment(values);
+        let mut cpu = Cpu::new(self.program);
+        for (&sym, &x) in &assignment {
+            if let Symbol::Mem(addr) = sym {
+                cpu.mem[addr] = x;
+            }
+        }
+        for input in &self.inputs {
+            cpu.add_input(input.eval(&assignment)?);
+        }
+        // like a search, which does not take runs that hit the step limit
+        match cpu.run_for(self.max_steps) {
+            Ok(State::StepLimitReached) | Err(_) => return None,
+            Ok(_) => (),
+        }
+        match goal {
+            Goal::Mem(addr) => Some(cpu.mem[addr]),
+            Goal::Output(n) => cpu.outputs.get(n).copied(),
+        }
+    }
+
+    /// A search over the symbols, run from `init`.
+    fn search<'s>(&'s self, init: &'s Cpu) -> Search<'s, Cpu> {
+        let mut search = Search::new(init);
+        search.max_steps = self.max_steps;
+        // symbolic inputs come up in the order they are queued, so the known
+        // inputs before each can be queued as it does
+        let mut queued = 0;
+        for (sym, bounds) in &self.symbols {
+            match *sym {
+                Symbol::Mem(addr) => {
+                    search.mem(addr, bounds.clone());
+                }
+                Symbol::Input(n) => {
+                    for x in &self.inputs[queued..n] {
+                        search.known_input(x.as_const().unwrap());
+                    }
+                    search.input(bounds.clone());
+                    queued = n + 1;
+                }
+            }
+        }
+        for x in &self.inputs[queued..] {
+            search.known_input(x.as_const().unwrap());
+        }
+        search
+    }
+
+    /// Runs the program for every combination, on all cores, skipping those
+    /// for which `expr`, if given, does not come out as `target`.
+    fn execution_search(&self, goal: Goal, target: i128, expr: Option<&Expr>) -> Option<Vec<i128>> {
+        let init = Cpu::new(self.program);
+        let mut search = self.search(&init);
+        if let Some(expr) = expr {
+            let n = self.symbols.len();
+            search.prune(move |values| {
+                values.len() == n && expr.eval(&self.assignment(values)) != Some(target)
+            });
+        }
+        search.find(|cpu| match goal {
+            Goal::Mem(addr) => cpu.mem[addr] == target,
+            Goal::Output(n) => cpu.outputs.get(n) == Some(&target),
+        })
+    }
+
+    /// Solves `constant + sum(coeff * symbol) == target`, giving the same
+    /// answer as trying every combination would: the last symbol with a
+    /// coefficient is solved for, the others before it are searched and
+    /// the rest keep their lowest value.
+    fn solve_linear(&self, linear: &Linear, target: i128) -> Option<Vec<i128>> {
+        let coeff = |i: usize| linear.coeffs.get(&self.symbols[i].0).copied().unwrap_or(0);
+        let last = (0..self.symbols.len()).rev().find(|&i| coeff(i) != 0);
+        let mut fixed = self.clone();
+        for (i, (_, bounds)) in fixed.symbols.iter_mut().enumerate() {
+            if !bounds.is_empty() && (last.is_none_or(|last| i >= last) || coeff(i) == 0) {
+                *bounds = *bounds.start()..=*bounds.start();
+            }
+        }
+        // the value of the last symbol that makes it work, if any
+        let solve = |values: &[i128]| -> Option<i128> {
+            let last = last?;
+            let rest = (0..last).try_fold(target.checked_sub(linear.constant)?, |rest, i| {
+                rest.checked_sub(coeff(i).checked_mul(values[i])?)
+            })?;
+            let (a, bounds) = (coeff(last), &self.symbols[last].1);
+            rest.checked_div(a).filter(|x| rest % a == 0 && bounds.contains(x))
+        };
+        let init = Cpu::new(self.program);
+        let mut values = fixed.search(&init).find_values(|values| match last {
+            Some(_) => solve(values).is_some(),
+            None => linear.constant == target,
+        })?;
+        if let Some(last) = last {
+            values[last] = solve(&values)?;
+        }
+        Some(values)
+    }
+
+    /// Finds symbol values for which `goal` ends up as `target`, trying
+    /// combinations in order, first symbol slowest, and returning the first
+    /// that works.
+    pub fn solve(&self, goal: Goal, target: i128) -> Option<Solution> {
+        let expr = match self.expr(goal) {
+            Ok(expr) if !expr.has_load() => expr,
+            _ => {
+                let values = self.execution_search(goal, target, None)?;
+                return Some(Solution { values, method: Method::Execution });
+            }
+        };
+        if let Some(linear) = expr.linear() {
+            // overflow in the actual run is the one thing the expression
+            // does not capture
+            match self.solve_linear(&linear, target) {
+                Some(values) if self.run(goal, &values) == Some(target) => {
+                    return Some(Solution { values, method: Method::Linear });
+                }
+                Some(_) => (),
+                None => return None,
+            }
+        }
+        let values = self.execution_search(goal, target, Some(&expr))?;
+        Some(Solution { values, method: Method::Expression })
+    }
+}
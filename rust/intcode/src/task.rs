@@ -0,0 +1,267 @@
+//! Running machines as futures, so many of them can share one thread.
+//!
+//! `Cpu::run_async` takes its input from a `Source` and sends its output
+//! into a `Sink`, waiting on either as needed. Any executor can drive it;
+//! `Executor` is a small single-threaded one, and `channel` makes a
+//! source/sink pair to connect machines with.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::{self, Future};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+
+use crate::cpu::{Cpu, State};
+use crate::error::Error;
+use crate::word::Word;
+
+// steps a machine runs before giving other tasks a turn
+const STEPS_PER_POLL: usize = 1024;
+
+/// Where a machine gets its input from.
+pub trait Source<W> {
+    /// The next input, or `None` if there will be no more.
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<W>>;
+}
+
+/// Where a machine sends its output.
+pub trait Sink<W> {
+    /// Ready once the sink can take another value.
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<()> {
+        Poll::Ready(())
+    }
+
+    fn send(&mut self, x: W);
+}
+
+/// Inputs known up front; never waits.
+impl<W> Source<W> for VecDeque<W> {
+    fn poll_recv(&mut self, _cx: &mut Context<'_>) -> Poll<Option<W>> {
+        Poll::Ready(self.pop_front())
+    }
+}
+
+impl<W> Sink<W> for Vec<W> {
+    fn send(&mut self, x: W) {
+        self.push(x);
+    }
+}
+
+impl<W, S: Source<W> + ?Sized> Source<W> for &mut S {
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<W>> {
+        (**self).poll_recv(cx)
+    }
+}
+
+impl<W, S: Sink<W> + ?Sized> Sink<W> for &mut S {
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        (**self).poll_ready(cx)
+    }
+
+    fn send(&mut self, x: W) {
+        (**self).send(x)
+    }
+}
+
+struct Queue<W> {
+    values: VecDeque<W>,
+    waker: Option<Waker>,
+    senders: usize,
+}
+
+/// Sending half of a `channel`.
+pub struct Sender<W> {
+    queue: Rc<RefCell<Queue<W>>>,
+}
+
+/// Receiving half of a `channel`. It runs dry once every sender is gone.
+pub struct Receiver<W> {
+    queue: Rc<RefCell<Queue<W>>>,
+}
+
+/// An unbounded single-threaded channel.
+pub fn channel<W>() -> (Sender<W>, Receiver<W>) {
+    let queue = Rc::new(RefCell::new(Queue { values: VecDeque::new(), waker: None, senders: 1 }));
+    (Sender { queue: Rc::clone(&queue) }, Receiver { queue })
+}
+
+impl<W> Sender<W> {
+    pub fn send(&self, x: W) {
+        let mut queue = self.queue.borrow_mut();
+        queue.values.push_back(x);
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<W> Clone for Sender<W> {
+    fn clone(&self) -> Sender<W> {
+        self.queue.borrow_mut().senders += 1;
+        Sender { queue: Rc::clone(&self.queue) }
+    }
+}
+
+impl<W> Drop for Sender<W> {
+    fn drop(&mut self) {
+        let mut queue = self.queue.borrow_mut();
+        queue.senders -= 1;
+        if queue.senders == 0 {
+            if let Some(waker) = queue.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl<W> Sink<W> for Sender<W> {
+    fn send(&mut self, x: W) {
+        Sender::send(self, x);
+    }
+}
+
+impl<W> Receiver<W> {
+    /// Takes a value without waiting.
+    pub fn try_recv(&self) -> Option<W> {
+        self.queue.borrow_mut().values.pop_front()
+    }
+}
+
+impl<W> Source<W> for Receiver<W> {
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<W>> {
+        let mut queue = self.queue.borrow_mut();
+        match queue.values.pop_front() {
+            Some(x) => Poll::Ready(Some(x)),
+            None if queue.senders == 0 => Poll::Ready(None),
+            None => {
+                queue.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Lets the executor run other tasks before continuing.
+fn yield_now() -> impl Future<Output = ()> {
+    let mut yielded = false;
+    future::poll_fn(move |cx| {
+        if yielded {
+            Poll::Ready(())
+        } else {
+            yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    })
+}
+
+impl<W: Word> Cpu<W> {
+    /// `run`, except that input is awaited from `input` and output is sent
+    /// to `output` rather than buffered. Returns `State::NeedsInput` only
+    /// once `input` has run dry.
+    pub async fn run_async<I: Source<W>, O: Sink<W>>(&mut self, mut input: I, mut output: O) -> Result<State<W>, Error<W>> {
+        let mut steps = 0;
+        loop {
+            steps += 1;
+            if steps % STEPS_PER_POLL == 0 {
+                yield_now().await;
+            }
+            match self.step()? {
+                State::Running => (),
+                State::Output(x) => {
+                    future::poll_fn(|cx| output.poll_ready(cx)).await;
+                    output.send(x);
+                }
+                State::NeedsInput => match future::poll_fn(|cx| input.poll_recv(cx)).await {
+                    Some(x) => self.add_input(x),
+                    None => return Ok(State::NeedsInput),
+                },
+                state => return Ok(state),
+            }
+        }
+    }
+}
+
+struct TaskWaker {
+    id: usize,
+    ready: Arc<Mutex<VecDeque<usize>>>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.ready.lock().unwrap().push_back(self.id);
+    }
+}
+
+/// The result of a spawned task, once it has finished.
+pub struct Task<T> {
+    result: Rc<RefCell<Option<T>>>,
+}
+
+impl<T> Task<T> {
+    pub fn take(&self) -> Option<T> {
+        self.result.borrow_mut().take()
+    }
+}
+
+type LocalFuture<'a> = Pin<Box<dyn Future<Output = ()> + 'a>>;
+
+/// Polls its tasks on the current thread until none of them can make
+/// progress.
+#[derive(Default)]
+pub struct Executor<'a> {
+    tasks: Vec<Option<LocalFuture<'a>>>,
+    ready: Arc<Mutex<VecDeque<usize>>>,
+}
+
+impl<'a> Executor<'a> {
+    pub fn new() -> Executor<'a> {
+        Executor::default()
+    }
+
+    pub fn spawn<T: 'a>(&mut self, future: impl Future<Output = T> + 'a) -> Task<T> {
+        let result = Rc::new(RefCell::new(None));
+        let slot = Rc::clone(&result);
+        self.tasks.push(Some(Box::pin(async move {
+            let value = future.await;
+            *slot.borrow_mut() = Some(value);
+        })));
+        self.ready.lock().unwrap().push_back(self.tasks.len() - 1);
+        Task { result }
+    }
+
+    /// Runs until every task has finished or is waiting for something no
+    /// other task will do, and returns how many tasks are left unfinished.
+    pub fn run(&mut self) -> usize {
+        loop {
+            let id = match self.ready.lock().unwrap().pop_front() {
+                Some(id) => id,
+                None => break,
+            };
+            let task = match &mut self.tasks[id] {
+                Some(task) => task,
+                None => continue,
+            };
+            let waker = Waker::from(Arc::new(TaskWaker { id, ready: Arc::clone(&self.ready) }));
+            if task.as_mut().poll(&mut Context::from_waker(&waker)).is_ready() {
+                self.tasks[id] = None;
+            }
+        }
+        self.tasks.iter().filter(|task| task.is_some()).count()
+    }
+}
+
+/// Runs a single future to completion on the current thread. `None` if it
+/// got stuck waiting.
+pub fn block_on<T>(future: impl Future<Output = T>) -> Option<T> {
+    let mut executor = Executor::new();
+    let task = executor.spawn(future);
+    executor.run();
+    task.take()
+}
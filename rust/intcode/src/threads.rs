@@ -0,0 +1,331 @@
+//! Machines running on their own threads, talking over channels.
+//!
+//! `spawn` gives a single machine an input and an output channel. A
+//! `Network` wires several machines together, output to input, and runs
+//! them until every one has halted or the whole network is stuck waiting
+//! for input with nothing left in flight, or a machine fails or uses up its
+//! step budget.
+
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+
+use crate::aot::Machine;
+use crate::cpu::{Cpu, State};
+use crate::error::Error;
+use crate::limits::LimitError;
+use crate::word::Word;
+
+/// Something that runs like a `Cpu`, so it can be put on a thread.
+pub trait Run<W>: Send + 'static {
+    fn pc(&self) -> usize;
+    fn add_input(&mut self, x: W);
+    fn step(&mut self) -> Result<State<W>, Error<W>>;
+    fn run_until_output(&mut self) -> Result<State<W>, Error<W>>;
+}
+
+impl<W: Word> Run<W> for Cpu<W> {
+    fn pc(&self) -> usize {
+        self.pc
+    }
+
+    fn add_input(&mut self, x: W) {
+        Cpu::add_input(self, x);
+    }
+
+    fn step(&mut self) -> Result<State<W>, Error<W>> {
+        Cpu::step(self)
+    }
+
+    fn run_until_output(&mut self) -> Result<State<W>, Error<W>> {
+        Cpu::run_until_output(self)
+    }
+}
+
+impl Run<i128> for Machine {
+    fn pc(&self) -> usize {
+        self.pc
+    }
+
+    fn add_input(&mut self, x: i128) {
+        Machine::add_input(self, x);
+    }
+
+    fn step(&mut self) -> Result<State<i128>, Error<i128>> {
+        Machine::step(self)
+    }
+
+    fn run_until_output(&mut self) -> Result<State<i128>, Error<i128>> {
+        Machine::run_until_output(self)
+    }
+}
+
+// `run_until_output` within a budget of steps, which like a `Guard`'s does
+// not count waiting for input
+fn run_until_output<W, M: Run<W>>(machine: &mut M, steps: &mut u64, max_steps: Option<u64>) -> Result<State<W>, LimitError<W>> {
+    let Some(max) = max_steps else {
+        return Ok(machine.run_until_output()?);
+    };
+    loop {
+        if *steps >= max {
+            return Err(LimitError::Steps { pc: machine.pc(), steps: *steps });
+        }
+        match machine.step()? {
+            State::NeedsInput => return Ok(State::NeedsInput),
+            State::Running => *steps += 1,
+            state => {
+                *steps += 1;
+                return Ok(state);
+            }
+        }
+    }
+}
+
+/// A machine running on its own thread.
+pub struct Handle<W, M> {
+    pub input: Sender<W>,
+    pub output: Receiver<W>,
+    thread: JoinHandle<Result<M, Error<W>>>,
+}
+
+impl<W, M> Handle<W, M> {
+    /// Waits for the machine to halt, or to need input after `input` and
+    /// every clone of it has been dropped, and returns it.
+    pub fn join(self) -> Result<M, Error<W>> {
+        drop(self.input);
+        self.thread.join().expect("machine thread panicked")
+    }
+}
+
+/// Starts running `machine` on a new thread. Its outputs go to
+/// `Handle::output` and it takes input from `Handle::input`.
+pub fn spawn<W: Word, M: Run<W>>(mut machine: M) -> Handle<W, M> {
+    let (input, inputs) = mpsc::channel();
+    let (outputs, output) = mpsc::channel();
+    let thread = thread::spawn(move || loop {
+        match machine.run_until_output()? {
+            State::Output(x) => {
+                // nobody listening is not the machine's problem
+                let _ = outputs.send(x);
+            }
+            State::NeedsInput => match inputs.recv() {
+                Ok(x) => machine.add_input(x),
+                Err(_) => return Ok(machine),
+            },
+            _ => return Ok(machine),
+        }
+    });
+    Handle { input, output, thread }
+}
+
+enum Msg<W> {
+    Value(W),
+    Stop,
+}
+
+struct Status {
+    waiting: Vec<bool>,
+    done: Vec<bool>,
+    // values sent to machines that have not taken them yet
+    in_flight: usize,
+    failed: bool,
+}
+
+impl Status {
+    fn settled(&self) -> bool {
+        self.failed
+            || (self.in_flight == 0 && self.waiting.iter().zip(&self.done).all(|(&w, &d)| w || d))
+    }
+}
+
+struct Shared {
+    status: Mutex<Status>,
+    changed: Condvar,
+}
+
+/// An error of one machine of a network, or its step budget running out.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct NodeError<W = i128> {
+    pub node: usize,
+    pub error: LimitError<W>,
+}
+
+impl<W: Word> fmt::Display for NodeError<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "machine {}: {}", self.node, self.error)
+    }
+}
+
+impl<W: Word> std::error::Error for NodeError<W> {}
+
+/// How a network run ended.
+pub struct Outcome<W, M> {
+    /// The machines, in the order they were added.
+    pub machines: Vec<M>,
+    /// Everything each machine output.
+    pub outputs: Vec<Vec<W>>,
+    /// True if the network stopped with some machines still waiting for
+    /// input rather than with every machine halted.
+    pub quiesced: bool,
+}
+
+/// Machines with their outputs wired to other machines' inputs. Every
+/// output of a machine is sent to each machine it is connected to; initial
+/// inputs are whatever is queued in the machines when the network runs.
+pub struct Network<M> {
+    machines: Vec<M>,
+    links: Vec<(usize, usize)>,
+    /// Steps each machine may take in all, so one spinning forever cannot
+    /// hang the network. Unlimited by default.
+    pub max_steps: Option<u64>,
+}
+
+impl<M> Network<M> {
+    /// Machines with no connections yet.
+    pub fn new(machines: Vec<M>) -> Network<M> {
+        Network { machines, links: Vec::new(), max_steps: None }
+    }
+
+    /// Every machine feeding the next one.
+    pub fn pipeline(machines: Vec<M>) -> Network<M> {
+        let mut network = Network::new(machines);
+        for i in 1..network.machines.len() {
+            network.connect(i - 1, i);
+        }
+        network
+    }
+
+    /// A pipeline whose last machine feeds the first one.
+    pub fn ring(machines: Vec<M>) -> Network<M> {
+        let mut network = Network::pipeline(machines);
+        if let Some(last) = network.machines.len().checked_sub(1) {
+            network.connect(last, 0);
+        }
+        network
+    }
+
+    /// Sends the outputs of machine `from` to machine `to`.
+    pub fn connect(&mut self, from: usize, to: usize) -> &mut Network<M> {
+        assert!(from < self.machines.len() && to < self.machines.len(), "no such machine");
+        self.links.push((from, to));
+        self
+    }
+
+    /// Runs every machine on its own thread until the network halts,
+    /// quiesces or one of the machines fails or runs out of steps.
+    pub fn run<W: Word>(self) -> Result<Outcome<W, M>, NodeError<W>>
+    where
+        M: Run<W>,
+    {
+        let n = self.machines.len();
+        let shared = Arc::new(Shared {
+            status: Mutex::new(Status { waiting: vec![false; n], done: vec![false; n], in_flight: 0, failed: false }),
+            changed: Condvar::new(),
+        });
+        let (senders, receivers): (Vec<_>, Vec<_>) = (0..n).map(|_| mpsc::channel::<Msg<W>>()).unzip();
+
+        let mut threads = Vec::new();
+        for (id, (machine, inbox)) in self.machines.into_iter().zip(receivers).enumerate() {
+            let targets: Vec<(usize, Sender<Msg<W>>)> = self.links.iter()
+                .filter(|&&(from, _)| from == id)
+                .map(|&(_, to)| (to, senders[to].clone()))
+                .collect();
+            let shared = Arc::clone(&shared);
+            let max_steps = self.max_steps;
+            threads.push(thread::spawn(move || node(id, machine, max_steps, inbox, targets, &shared)));
+        }
+
+        let mut status = shared.status.lock().unwrap();
+        while !status.settled() {
+            status = shared.changed.wait(status).unwrap();
+        }
+        let quiesced = !status.done.iter().all(|&d| d);
+        drop(status);
+        for sender in &senders {
+            let _ = sender.send(Msg::Stop);
+        }
+
+        let mut outcome = Outcome { machines: Vec::new(), outputs: Vec::new(), quiesced };
+        let mut failure = None;
+        for (id, thread) in threads.into_iter().enumerate() {
+            let (machine, outputs, result) = thread.join().expect("machine thread panicked");
+            if let (Err(error), None) = (result, &failure) {
+                failure = Some(NodeError { node: id, error });
+            }
+            outcome.machines.push(machine);
+            outcome.outputs.push(outputs);
+        }
+        match failure {
+            Some(err) => Err(err),
+            None => Ok(outcome),
+        }
+    }
+}
+
+type NodeResult<W, M> = (M, Vec<W>, Result<(), LimitError<W>>);
+
+fn node<W: Word, M: Run<W>>(
+    id: usize,
+    mut machine: M,
+    max_steps: Option<u64>,
+    inbox: Receiver<Msg<W>>,
+    targets: Vec<(usize, Sender<Msg<W>>)>,
+    shared: &Shared,
+) -> NodeResult<W, M> {
+    let mut outputs = Vec::new();
+    let mut steps = 0;
+    let finish = |failed: bool| {
+        let mut status = shared.status.lock().unwrap();
+        status.done[id] = true;
+        status.failed |= failed;
+        // nobody will take what was already sent here
+        while let Ok(msg) = inbox.try_recv() {
+            if let Msg::Value(_) = msg {
+                status.in_flight -= 1;
+            }
+        }
+        shared.changed.notify_all();
+    };
+    loop {
+        match run_until_output(&mut machine, &mut steps, max_steps) {
+            Ok(State::Output(x)) => {
+                let mut status = shared.status.lock().unwrap();
+                // sent with the lock held, so a target cannot finish between
+                // being counted and being sent to
+                for (to, sender) in &targets {
+                    if !status.done[*to] && sender.send(Msg::Value(x.clone())).is_ok() {
+                        status.in_flight += 1;
+                    }
+                }
+                drop(status);
+                outputs.push(x);
+            }
+            Ok(State::NeedsInput) => {
+                {
+                    let mut status = shared.status.lock().unwrap();
+                    status.waiting[id] = true;
+                    shared.changed.notify_all();
+                }
+                match inbox.recv() {
+                    Ok(Msg::Value(x)) => {
+                        let mut status = shared.status.lock().unwrap();
+                        status.waiting[id] = false;
+                        status.in_flight -= 1;
+                        drop(status);
+                        machine.add_input(x);
+                    }
+                    Ok(Msg::Stop) | Err(_) => return (machine, outputs, Ok(())),
+                }
+            }
+            Ok(_) => {
+                finish(false);
+                return (machine, outputs, Ok(()));
+            }
+            Err(err) => {
+                finish(true);
+                return (machine, outputs, Err(err));
+            }
+        }
+    }
+}
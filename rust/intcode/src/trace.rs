@@ -0,0 +1,204 @@
+//! Execution traces: every executed instruction with the memory it read
+//! and wrote, the input it consumed and the output it produced.
+//!
+//! Traces are stored one step per line after an `intcode-trace 1` header:
+//!
+//! ```text
+//! 0 IN w8=0 i0
+//! 2 JT r8=0
+//! 11 OUT o1
+//! ```
+//!
+//! `rA=V` reads V from address A, `wA=V` writes V to A, `iV` consumes the
+//! input V and `oV` outputs V.
+
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use crate::cpu::{Cpu, Observer, State};
+use crate::error::Error;
+use crate::op::Op;
+use crate::word::Word;
+
+const HEADER: &str = "intcode-trace 1";
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Step<W> {
+    pub pc: usize,
+    pub op: Op,
+    pub reads: Vec<(usize, W)>,
+    pub writes: Vec<(usize, W)>,
+    pub input: Option<W>,
+    pub output: Option<W>,
+}
+
+impl<W: fmt::Display> fmt::Display for Step<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.pc, self.op.mnemonic())?;
+        for (addr, value) in &self.reads {
+            write!(f, " r{}={}", addr, value)?;
+        }
+        for (addr, value) in &self.writes {
+            write!(f, " w{}={}", addr, value)?;
+        }
+        if let Some(x) = &self.input {
+            write!(f, " i{}", x)?;
+        }
+        if let Some(x) = &self.output {
+            write!(f, " o{}", x)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_step<W: Word>(line: &str) -> Option<Step<W>> {
+    let mut tokens = line.split_whitespace();
+    let pc = tokens.next()?.parse().ok()?;
+    let op = Op::from_mnemonic(tokens.next()?)?;
+    let mut step = Step { pc, op, reads: Vec::new(), writes: Vec::new(), input: None, output: None };
+    let access = |rest: &str| -> Option<(usize, W)> {
+        let (addr, value) = rest.split_once('=')?;
+        Some((addr.parse().ok()?, value.parse().ok()?))
+    };
+    for token in tokens {
+        let (kind, rest) = token.split_at_checked(1)?;
+        match kind {
+            "r" => step.reads.push(access(rest)?),
+            "w" => step.writes.push(access(rest)?),
+            "i" => step.input = Some(rest.parse().ok()?),
+            "o" => step.output = Some(rest.parse().ok()?),
+            _ => return None,
+        }
+    }
+    Some(step)
+}
+
+/// Observer that records every instruction a machine executes. Drive the
+/// machine through `Tracer::step` or `Tracer::run` so outputs are recorded
+/// as well.
+#[derive(Clone, Debug)]
+pub struct Tracer<W> {
+    pub steps: Vec<Step<W>>,
+}
+
+impl<W> Default for Tracer<W> {
+    fn default() -> Tracer<W> {
+        Tracer::new()
+    }
+}
+
+impl<W> Tracer<W> {
+    pub fn new() -> Tracer<W> {
+        Tracer { steps: Vec::new() }
+    }
+}
+
+impl<W: Word> Tracer<W> {
+    pub fn step(&mut self, cpu: &mut Cpu<W>) -> Result<State<W>, Error<W>> {
+        let state = cpu.step_with(self)?;
+        if let (State::Output(x), Some(step)) = (&state, self.steps.last_mut()) {
+            step.output = Some(x.clone());
+        }
+        Ok(state)
+    }
+
+    /// Traced equivalent of `Cpu::run`.
+    pub fn run(&mut self, cpu: &mut Cpu<W>) -> Result<State<W>, Error<W>> {
+        loop {
+            match self.step(cpu)? {
+                State::Running => continue,
+                State::Output(x) => cpu.outputs.push(x),
+                state => return Ok(state),
+            }
+        }
+    }
+
+    pub fn save<T: Write>(&self, mut out: T) -> io::Result<()> {
+        writeln!(out, "{}", HEADER)?;
+        for step in &self.steps {
+            writeln!(out, "{}", step)?;
+        }
+        Ok(())
+    }
+
+    pub fn load<T: BufRead>(input: T) -> io::Result<Tracer<W>> {
+        let bad_data = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let mut lines = input.lines();
+        match lines.next() {
+            Some(Ok(line)) if line.trim() == HEADER => (),
+            Some(Err(err)) => return Err(err),
+            _ => return Err(bad_data("not an intcode trace".to_string())),
+        }
+        let mut steps = Vec::new();
+        for (i, line) in lines.enumerate() {
+            let line = line?;
+            let step = parse_step(&line)
+                .ok_or_else(|| bad_data(format!("line {}: bad trace step `{}`", i + 2, line)))?;
+            steps.push(step);
+        }
+        Ok(Tracer { steps })
+    }
+}
+
+impl<W: Word> Observer<W> for Tracer<W> {
+    fn exec(&mut self, pc: usize, op: Op) {
+        self.steps.push(Step { pc, op, reads: Vec::new(), writes: Vec::new(), input: None, output: None });
+    }
+
+    fn read(&mut self, _pc: usize, addr: usize, value: &W) {
+        if let Some(step) = self.steps.last_mut() {
+            step.reads.push((addr, value.clone()));
+        }
+    }
+
+    fn write(&mut self, _pc: usize, addr: usize, _old: &W, new: &W) {
+        if let Some(step) = self.steps.last_mut() {
+            if step.op == Op::Input {
+                step.input = Some(new.clone());
+            }
+            step.writes.push((addr, new.clone()));
+        }
+    }
+}
+
+/// The first step at which a replay stopped matching its trace.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Divergence<W> {
+    pub index: usize,
+    pub expected: Step<W>,
+    /// What the machine did instead; `None` if it stopped without executing
+    /// anything.
+    pub actual: Option<Step<W>>,
+    pub error: Option<Error<W>>,
+}
+
+impl<W: Word> fmt::Display for Divergence<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "step {}: expected `{}`, ", self.index, self.expected)?;
+        match (&self.actual, &self.error) {
+            (_, Some(err)) => write!(f, "got error: {}", err),
+            (Some(actual), None) => write!(f, "got `{}`", actual),
+            (None, None) => write!(f, "machine stopped"),
+        }
+    }
+}
+
+/// Re-executes `program` against a recorded trace, feeding it the inputs
+/// the trace consumed, and returns the number of matching steps.
+pub fn replay<W: Word>(program: &[W], trace: &[Step<W>]) -> Result<usize, Box<Divergence<W>>> {
+    let mut cpu = Cpu::new(program);
+    for (index, expected) in trace.iter().enumerate() {
+        if let Some(x) = &expected.input {
+            cpu.inputs.clear();
+            cpu.add_input(x.clone());
+        }
+        let mut tracer = Tracer::new();
+        let result = tracer.step(&mut cpu);
+        let actual = tracer.steps.pop();
+        let error = result.err();
+        if actual.as_ref() != Some(expected) || error.is_some() {
+            return Err(Box::new(Divergence { index, expected: expected.clone(), actual, error }));
+        }
+    }
+    Ok(trace.len())
+}
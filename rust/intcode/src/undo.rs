@@ -0,0 +1,106 @@
+//! Undo log for stepping a machine backwards one instruction at a time.
+
+use std::collections::VecDeque;
+
+use crate::cpu::{Cpu, Observer, State};
+use crate::error::Error;
+use crate::word::Word;
+
+// steps kept before the oldest ones are forgotten
+const DEFAULT_LIMIT: usize = 1 << 20;
+
+/// Everything one instruction changed, enough to put the machine back the
+/// way it was before it ran.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Change<W> {
+    pub pc: usize,
+    pub base_offset: i64,
+    /// Overwritten memory cells with their previous values, in write order.
+    pub writes: Vec<(usize, W)>,
+    pub input: Option<W>,
+    pub output: bool,
+}
+
+struct Writes<W>(Vec<(usize, W)>);
+
+impl<W: Clone> Observer<W> for Writes<W> {
+    fn write(&mut self, _pc: usize, addr: usize, old: &W, _new: &W) {
+        self.0.push((addr, old.clone()));
+    }
+}
+
+/// The most recent steps of a machine, newest last.
+#[derive(Clone, Debug)]
+pub struct History<W> {
+    changes: VecDeque<Change<W>>,
+    pub limit: usize,
+}
+
+impl<W> Default for History<W> {
+    fn default() -> History<W> {
+        History::new()
+    }
+}
+
+impl<W> History<W> {
+    pub fn new() -> History<W> {
+        History { changes: VecDeque::new(), limit: DEFAULT_LIMIT }
+    }
+
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.changes.clear();
+    }
+
+    /// The change `undo` would revert next.
+    pub fn last(&self) -> Option<&Change<W>> {
+        self.changes.back()
+    }
+}
+
+impl<W: Word> History<W> {
+    /// Steps `cpu` like `Cpu::step_with`, remembering how to undo the step.
+    /// The caller is expected to buffer outputs in `cpu.outputs`.
+    pub fn step<O: Observer<W>>(&mut self, cpu: &mut Cpu<W>, obs: O) -> Result<State<W>, Error<W>> {
+        let (pc, base_offset) = (cpu.pc, cpu.base_offset);
+        let (input, inputs) = (cpu.inputs.front().cloned(), cpu.inputs.len());
+        let mut writes = Writes(Vec::new());
+        let state = cpu.step_with(&mut (obs, &mut writes))?;
+        if matches!(state, State::Running | State::Output(_)) {
+            let input = if cpu.inputs.len() < inputs { input } else { None };
+            let output = matches!(state, State::Output(_));
+            if self.changes.len() == self.limit {
+                self.changes.pop_front();
+            }
+            if self.limit > 0 {
+                self.changes.push_back(Change { pc, base_offset, writes: writes.0, input, output });
+            }
+        }
+        Ok(state)
+    }
+
+    /// Reverts the newest recorded step, returning it, or `None` if there
+    /// is nothing left to undo.
+    pub fn undo(&mut self, cpu: &mut Cpu<W>) -> Option<Change<W>> {
+        let change = self.changes.pop_back()?;
+        for (addr, old) in change.writes.iter().rev() {
+            cpu.mem[*addr] = old.clone();
+        }
+        cpu.pc = change.pc;
+        cpu.base_offset = change.base_offset;
+        if let Some(x) = &change.input {
+            cpu.inputs.push_front(x.clone());
+        }
+        if change.output {
+            cpu.outputs.pop();
+        }
+        Some(change)
+    }
+}
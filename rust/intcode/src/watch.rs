@@ -0,0 +1,104 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Range;
+
+use crate::cpu::Observer;
+use crate::op::Op;
+
+/// Watches an address range for reads, writes or both.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Watchpoint {
+    pub range: Range<usize>,
+    pub read: bool,
+    pub write: bool,
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Event<W> {
+    Read { pc: usize, addr: usize, value: W },
+    Write { pc: usize, addr: usize, old: W, new: W },
+    /// A write into a word that was already executed as part of an
+    /// instruction.
+    CodeWrite { pc: usize, addr: usize, old: W, new: W },
+}
+
+impl<W: fmt::Display> fmt::Display for Event<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::Read { pc, addr, value } => {
+                write!(f, "pc {} read [{}] = {}", pc, addr, value)
+            }
+            Event::Write { pc, addr, old, new } => {
+                write!(f, "pc {} wrote [{}]: {} -> {}", pc, addr, old, new)
+            }
+            Event::CodeWrite { pc, addr, old, new } => {
+                write!(f, "pc {} modified executed code at [{}]: {} -> {}", pc, addr, old, new)
+            }
+        }
+    }
+}
+
+/// Observer that records accesses to watched ranges and, optionally, writes
+/// that land on code the machine has already executed.
+#[derive(Clone, Debug)]
+pub struct Watcher<W> {
+    pub watchpoints: Vec<Watchpoint>,
+    pub detect_code_writes: bool,
+    pub events: Vec<Event<W>>,
+    executed: HashSet<usize>,
+}
+
+impl<W> Watcher<W> {
+    pub fn new() -> Watcher<W> {
+        Watcher {
+            watchpoints: Vec::new(),
+            detect_code_writes: false,
+            events: Vec::new(),
+            executed: HashSet::new(),
+        }
+    }
+
+    pub fn watch(&mut self, range: Range<usize>, read: bool, write: bool) {
+        self.watchpoints.push(Watchpoint { range, read, write });
+    }
+
+    /// Removes every watchpoint that covers `addr`.
+    pub fn unwatch(&mut self, addr: usize) {
+        self.watchpoints.retain(|w| !w.range.contains(&addr));
+    }
+
+    /// Whether a watchpoint covers writes (or reads) of `addr`.
+    pub fn watched(&self, addr: usize, write: bool) -> bool {
+        self.watchpoints.iter()
+            .any(|w| w.range.contains(&addr) && if write { w.write } else { w.read })
+    }
+}
+
+impl<W> Default for Watcher<W> {
+    fn default() -> Watcher<W> {
+        Watcher::new()
+    }
+}
+
+impl<W: Clone> Observer<W> for Watcher<W> {
+    fn exec(&mut self, pc: usize, op: Op) {
+        if self.detect_code_writes {
+            self.executed.extend(pc..pc + op.n_params() + 1);
+        }
+    }
+
+    fn read(&mut self, pc: usize, addr: usize, value: &W) {
+        if self.watched(addr, false) {
+            self.events.push(Event::Read { pc, addr, value: value.clone() });
+        }
+    }
+
+    fn write(&mut self, pc: usize, addr: usize, old: &W, new: &W) {
+        if self.detect_code_writes && self.executed.contains(&addr) {
+            self.events.push(Event::CodeWrite { pc, addr, old: old.clone(), new: new.clone() });
+        }
+        if self.watched(addr, true) {
+            self.events.push(Event::Write { pc, addr, old: old.clone(), new: new.clone() });
+        }
+    }
+}
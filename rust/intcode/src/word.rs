@@ -0,0 +1,86 @@
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+use std::str::FromStr;
+
+use crate::bigint::BigInt;
+
+/// A memory cell of an Intcode machine. Implemented for `i64`, `i128` and
+/// the arbitrary-precision `BigInt`.
+pub trait Word: Clone + Default + Eq + Ord + Hash + Debug + Display + FromStr + Send + Sync + 'static {
+    fn from_i64(x: i64) -> Self;
+    fn to_i128(&self) -> Option<i128>;
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+    fn checked_mul(&self, other: &Self) -> Option<Self>;
+    fn wrapping_add(&self, other: &Self) -> Self;
+    fn wrapping_mul(&self, other: &Self) -> Self;
+
+    fn is_zero(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// What arithmetic instructions do when the result does not fit a word.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Overflow {
+    Wrap,
+    Check,
+}
+
+macro_rules! primitive_word {
+    ($t:ty) => {
+        impl Word for $t {
+            fn from_i64(x: i64) -> $t {
+                x as $t
+            }
+
+            fn to_i128(&self) -> Option<i128> {
+                Some(*self as i128)
+            }
+
+            fn checked_add(&self, other: &$t) -> Option<$t> {
+                <$t>::checked_add(*self, *other)
+            }
+
+            fn checked_mul(&self, other: &$t) -> Option<$t> {
+                <$t>::checked_mul(*self, *other)
+            }
+
+            fn wrapping_add(&self, other: &$t) -> $t {
+                <$t>::wrapping_add(*self, *other)
+            }
+
+            fn wrapping_mul(&self, other: &$t) -> $t {
+                <$t>::wrapping_mul(*self, *other)
+            }
+        }
+    };
+}
+
+primitive_word!(i64);
+primitive_word!(i128);
+
+impl Word for BigInt {
+    fn from_i64(x: i64) -> BigInt {
+        BigInt::from(x as i128)
+    }
+
+    fn to_i128(&self) -> Option<i128> {
+        BigInt::to_i128(self)
+    }
+
+    fn checked_add(&self, other: &BigInt) -> Option<BigInt> {
+        Some(self + other)
+    }
+
+    fn checked_mul(&self, other: &BigInt) -> Option<BigInt> {
+        Some(self * other)
+    }
+
+    fn wrapping_add(&self, other: &BigInt) -> BigInt {
+        self + other
+    }
+
+    fn wrapping_mul(&self, other: &BigInt) -> BigInt {
+        self * other
+    }
+}
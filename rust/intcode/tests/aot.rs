@@ -0,0 +1,66 @@
+use intcode::aot::{self, Machine};
+use intcode::{Cpu, State};
+
+// regenerate with `cargo test -p intcode --test aot -- --ignored`
+#[path = "compiled/selfmod.rs"]
+mod selfmod;
+
+const SELFMOD: &str = "
+        IN   [n]
+  loop: ADD  #0, #0, [x]      ; the first operand counts up by 10
+        OUT  [x]
+        ADD  [loop+1], #10, [loop+1]
+        ADD  [n], #-1, [n]
+        JT   [n], #loop
+        ARB  #x
+        OUT  rb+0
+        HLT
+     n: db   0
+     x: db   0
+";
+
+fn selfmod_program() -> Vec<i128> {
+    intcode::asm::assemble(SELFMOD).unwrap()
+}
+
+#[test]
+#[ignore]
+fn regenerate() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/compiled/selfmod.rs");
+    std::fs::write(path, aot::compile(&selfmod_program(), "tests/aot.rs")).unwrap();
+}
+
+#[test]
+fn generated_module_is_current() {
+    assert_eq!(selfmod::PROGRAM, &selfmod_program()[..]);
+    assert_eq!(aot::compile(&selfmod_program(), "tests/aot.rs"), include_str!("compiled/selfmod.rs"));
+}
+
+#[test]
+fn compiled_matches_interpreter() {
+    let mut cpu = Cpu::new(selfmod::PROGRAM);
+    let mut machine = selfmod::machine();
+    cpu.add_input(4);
+    machine.add_input(4);
+    assert_eq!(cpu.run(), Ok(State::Halted));
+    assert_eq!(machine.run(), Ok(State::Halted));
+    assert_eq!(cpu.outputs, vec![0, 10, 20, 30, 30]);
+    assert_eq!(machine.outputs, cpu.outputs);
+    assert_eq!(machine.mem.read(0, 40), cpu.mem.read(0, 40));
+}
+
+#[test]
+fn edits_through_deref_fall_back_to_the_interpreter() {
+    let mut machine = selfmod::machine();
+    // make the loop count down from 5 instead
+    machine.mem[3] = 5;
+    machine.mem[10] = -1;
+    machine.add_input(2);
+    assert_eq!(machine.run(), Ok(State::Halted));
+    assert_eq!(machine.outputs, vec![5, 4, 4]);
+
+    let mut interpreted = Machine::interpreted(selfmod::PROGRAM);
+    interpreted.add_input(0);
+    assert_eq!(interpreted.run_until_output(), Ok(State::Output(0)));
+    assert_eq!(interpreted.run_for(3), Ok(State::StepLimitReached));
+}
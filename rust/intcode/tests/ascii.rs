@@ -0,0 +1,60 @@
+use std::io::Cursor;
+
+use intcode::ascii::{self, Chunk};
+use intcode::{Cpu, State};
+
+// echoes its input, following each line with 1000 times the line number
+fn echo() -> Vec<i128> {
+    intcode::asm::assemble("
+        loop: IN  [c]
+              OUT [c]
+              EQ  [c], #10, [t]
+              JF  [t], #loop
+              ADD [n], #1000, [n]
+              OUT [n]
+              JT  #1, #loop
+        c:    db  0
+        t:    db  0
+        n:    db  0
+    ").unwrap()
+}
+
+#[test]
+fn encode_and_decode() {
+    assert_eq!(ascii::encode::<i128>("hi\n"), Ok(vec![104, 105, 10]));
+    assert_eq!(ascii::encode::<i128>("né"), Err(ascii::NotAscii { pos: 1, c: 'é' }));
+    let outputs: [i128; 7] = [104, 105, 1000, 10, -1, 200, 33];
+    assert_eq!(ascii::decode(&outputs), vec![
+        Chunk::Text("hi".to_string()),
+        Chunk::Value(1000),
+        Chunk::Text("\n".to_string()),
+        Chunk::Value(-1),
+        Chunk::Value(200),
+        Chunk::Text("!".to_string()),
+    ]);
+    assert_eq!(ascii::render(&outputs), "hi\n1000\n\n-1\n200\n!");
+
+    let mut cpu = Cpu::new(&echo());
+    assert!(cpu.add_line("a€").is_err());
+    assert!(cpu.inputs.is_empty());
+    cpu.add_line("abc").unwrap();
+    assert_eq!(cpu.run(), Ok(State::NeedsInput));
+    assert_eq!(cpu.take_text(), "abc\n1000\n");
+    assert!(cpu.outputs.is_empty());
+}
+
+#[test]
+fn interactive_session() {
+    let mut cpu = Cpu::new(&echo());
+    let mut out = Vec::new();
+    let state = ascii::interact(&mut cpu, Cursor::new("hi\r\nyo"), &mut out).unwrap();
+    assert_eq!(state, State::NeedsInput);
+    assert_eq!(String::from_utf8(out).unwrap(), "hi\n1000\nyo\n2000\n");
+
+    let err = ascii::interact(&mut cpu, Cursor::new("ça\n"), Vec::new()).unwrap_err();
+    assert_eq!(err.to_string(), "'ç' at 0 is not an ASCII character");
+
+    let mut broken = Cpu::<i128>::new(&[104, 65, 98]);
+    let err = ascii::interact(&mut broken, Cursor::new(""), Vec::new()).unwrap_err();
+    assert_eq!(err.to_string(), "unknown opcode in 98 at pc 2");
+}
@@ -0,0 +1,57 @@
+use intcode::{Cpu, State};
+
+#[test]
+fn writes_drop_cached_instructions() {
+    // echoes its input forever
+    let program = intcode::asm::assemble("
+        loop: IN  [x]
+              OUT [x]
+              JT  #1, #loop
+        x:    db  0
+    ").unwrap();
+    let mut cpu = Cpu::new(&program);
+    cpu.inputs.extend([1, 2]);
+    assert_eq!(cpu.run(), Ok(State::NeedsInput));
+    assert_eq!(cpu.outputs, vec![1, 2]);
+
+    // OUT [x] becomes OUT #x, then HLT replaces the jump, both cached by now
+    cpu.mem[2] = 104;
+    cpu.mem[4] = 99;
+    cpu.add_input(3);
+    assert_eq!(cpu.run(), Ok(State::Halted));
+    assert_eq!(cpu.outputs, vec![1, 2, 7]);
+}
+
+#[test]
+fn cache_does_not_change_results() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../d11.in");
+    let program: Vec<i128> = intcode::parse_program(&std::fs::read_to_string(path).unwrap()).unwrap();
+    let outputs = |cache: bool| {
+        let mut cpu = Cpu::new(&program);
+        cpu.mem.set_code_cache(cache);
+        for _ in 0..50 {
+            cpu.add_input(1);
+            cpu.run().unwrap();
+        }
+        (cpu.outputs, cpu.mem.read(0, cpu.mem.extent()))
+    };
+    assert_eq!(outputs(true), outputs(false));
+}
+
+#[test]
+fn snapshots_start_with_an_empty_cache() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../d09.in");
+    let program: Vec<i128> = intcode::parse_program(&std::fs::read_to_string(path).unwrap()).unwrap();
+    let mut cpu = Cpu::new(&program);
+    cpu.add_input(2);
+    assert_eq!(cpu.run_for(10_000), Ok(State::StepLimitReached));
+    let warm = cpu.mem.cached_instrs();
+    assert!(warm > 10);
+
+    let mut snapshot = cpu.snapshot();
+    assert_eq!(snapshot.mem.cached_instrs(), 0);
+    assert_eq!(cpu.mem.cached_instrs(), warm);
+    assert_eq!(cpu.run(), Ok(State::Halted));
+    assert_eq!(snapshot.run(), Ok(State::Halted));
+    assert_eq!(snapshot.outputs, cpu.outputs);
+}
@@ -0,0 +1,55 @@
+use intcode::cfg::Cfg;
+
+#[test]
+fn blocks_edges_and_dot() {
+    let program = intcode::asm::assemble("
+              IN  [x]
+        loop: ADD [x], #-1, [x]
+              JT  [x], #loop
+              MUL #done, #1, [ret]
+              JT  #1, #sub
+        done: HLT
+        sub:  OUT [x]
+              JT  #1, [ret]
+        x:    db  0
+        ret:  db  0
+    ").unwrap();
+    let cfg = Cfg::build(&program);
+    // `done` is only reached by returning through `ret`
+    let blocks: Vec<(usize, usize, Vec<usize>, bool)> = cfg.blocks.iter()
+        .map(|b| (b.start, b.end(), b.succs.clone(), b.unresolved))
+        .collect();
+    assert_eq!(blocks, vec![
+        (0, 2, vec![2], false),
+        (2, 9, vec![9, 2], false),
+        (9, 16, vec![17], false),
+        (17, 22, vec![], true),
+    ]);
+    assert_eq!(cfg.block_at(5).map(|b| b.start), Some(2));
+    assert_eq!(cfg.block_at(22), None);
+    assert_eq!(cfg.to_dot(), r#"digraph intcode {
+    node [shape=box, fontname="monospace"];
+    b0 [label="    0: IN   [22]\l"];
+    b2 [label="    2: ADD  [22], #-1, [22]\l    6: JT   [22], #2\l"];
+    b9 [label="    9: MUL  #16, #1, [23]\l   13: JT   #1, #17\l"];
+    b17 [label="   17: OUT  [22]\l   19: JT   #1, [23]\l"];
+    indirect [shape=ellipse, style=dashed];
+    b0 -> b2;
+    b2 -> b9;
+    b2 -> b2 [label="JT"];
+    b9 -> b17 [label="JT"];
+    b17 -> indirect [style=dashed];
+}
+"#);
+}
+
+#[test]
+fn d11_graph() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../d11.in");
+    let program: Vec<i128> = intcode::parse_program(&std::fs::read_to_string(path).unwrap()).unwrap();
+    let cfg = Cfg::build(&program);
+    assert_eq!(cfg.blocks.len(), 17);
+    let unresolved: Vec<usize> = cfg.blocks.iter().filter(|b| b.unresolved).map(|b| b.start).collect();
+    assert_eq!(unresolved, vec![626]);
+    assert_eq!(cfg.block_at(229).unwrap().succs, vec![308, 15]);
+}
@@ -0,0 +1,115 @@
+// Compiled from tests/aot.rs by intcode::aot; do not edit.
+
+use intcode::aot::{Context, Machine};
+use intcode::{Error, State};
+
+pub const PROGRAM: &[i128] = &[3, 24, 1101, 0, 0, 25, 4, 25, 1001, 3, 10, 3, 1001, 24, -1, 24, 1005, 24, 2, 109, 25, 204, 0, 99, 0, 0];
+const LENS: &[u8] = &[2, 0, 4, 0, 0, 0, 2, 0, 4, 2, 0, 2, 4, 0, 0, 0, 3, 0, 4, 2, 0, 2, 0, 1, 0, 0];
+
+pub fn machine() -> Machine {
+    Machine::new(PROGRAM, LENS, exec)
+}
+
+fn exec(m: &mut Context, pc: usize) -> Result<State, Error> {
+    Ok(match pc {
+        0 => {
+            let x = match m.cpu.inputs.front() {
+                Some(&x) => x,
+                None => return Ok(State::NeedsInput),
+            };
+            let addr = m.addr(24)?;
+            m.set(addr, x);
+            m.cpu.inputs.pop_front();
+            m.cpu.pc = 2;
+            State::Running
+        }
+        2 => {
+            let a = 0;
+            let b = 0;
+            let x = m.add(a, b)?;
+            let addr = m.addr(25)?;
+            m.set(addr, x);
+            m.cpu.pc = 6;
+            State::Running
+        }
+        6 => {
+            let a = m.get(m.addr(25)?);
+            m.cpu.pc = 8;
+            State::Output(a)
+        }
+        8 => {
+            let a = m.get(m.addr(3)?);
+            let b = 10;
+            let x = m.add(a, b)?;
+            let addr = m.addr(3)?;
+            m.set(addr, x);
+            m.cpu.pc = 12;
+            State::Running
+        }
+        9 => {
+            let x = match m.cpu.inputs.front() {
+                Some(&x) => x,
+                None => return Ok(State::NeedsInput),
+            };
+            let addr = m.addr(10)?;
+            m.set(addr, x);
+            m.cpu.inputs.pop_front();
+            m.cpu.pc = 11;
+            State::Running
+        }
+        11 => {
+            let x = match m.cpu.inputs.front() {
+                Some(&x) => x,
+                None => return Ok(State::NeedsInput),
+            };
+            let addr = m.addr(1001)?;
+            m.set(addr, x);
+            m.cpu.inputs.pop_front();
+            m.cpu.pc = 13;
+            State::Running
+        }
+        12 => {
+            let a = m.get(m.addr(24)?);
+            let b = -1;
+            let x = m.add(a, b)?;
+            let addr = m.addr(24)?;
+            m.set(addr, x);
+            m.cpu.pc = 16;
+            State::Running
+        }
+        16 => {
+            let a = m.get(m.addr(24)?);
+            let b = 2;
+            if a != 0 {
+                m.jump(b)?;
+            } else {
+                m.cpu.pc = 19;
+            }
+            State::Running
+        }
+        18 => {
+            let a = m.get(m.addr(109)?);
+            let b = m.get(m.addr(25)?);
+            let x = m.mul(a, b)?;
+            let addr = m.addr(204)?;
+            m.set(addr, x);
+            m.cpu.pc = 22;
+            State::Running
+        }
+        19 => {
+            let a = 25;
+            m.adjust_base(a)?;
+            m.cpu.pc = 21;
+            State::Running
+        }
+        21 => {
+            let a = m.get(m.rel(0)?);
+            m.cpu.pc = 23;
+            State::Output(a)
+        }
+        23 => {
+            State::Halted
+        }
+        _ => unreachable!("no compiled code at {}", pc),
+    })
+}
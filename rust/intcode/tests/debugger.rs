@@ -0,0 +1,88 @@
+use intcode::debugger::Debugger;
+use intcode::Cpu;
+
+fn session(program: &[i128], commands: &[&str]) -> Vec<String> {
+    let mut dbg = Debugger::new(Cpu::new(program));
+    commands.iter()
+        .map(|cmd| match dbg.command(cmd) {
+            Ok(out) => out,
+            Err(err) => format!("error: {}", err),
+        })
+        .collect()
+}
+
+#[test]
+fn breakpoints_and_stepping() {
+    // outputs its input doubled, forever
+    let program = intcode::asm::assemble("
+        loop: IN  [x]
+              MUL [x], #2, [x]
+              OUT [x]
+              JT  #1, #loop
+        x:    db  0
+    ").unwrap();
+    let out = session(&program, &[
+        "b OUT", "c", "in 4 5", "c", "c", "s", "out", "x 11 1", "set 11 -3", "s 2", "b", "d OUT", "c",
+    ]);
+    assert_eq!(out, vec![
+        "",
+        "waiting for input\n    0: IN   [11]",
+        "",
+        "breakpoint at 6\n    6: OUT  [11]",
+        "output: 8\nbreakpoint at 6\n    6: OUT  [11]",
+        "output: 10\n    8: JT   #1, #0",
+        "[8, 10]",
+        "   11: 10",
+        "",
+        "waiting for input\n    0: IN   [11]",
+        "OUT",
+        "",
+        "waiting for input\n    0: IN   [11]",
+    ]);
+}
+
+#[test]
+fn registers_and_errors() {
+    let out = session(&[109, 5, 204, 0, 99, 42], &[
+        "s", "rb", "rb 1", "s", "i", "pc 3", "s", "bogus", "x",
+        "x 18446744073709551615 2", "l 18446744073709551615 3", "x 0 3000000000", "l 0 1001",
+        "w 18446744073709551615",
+    ]);
+    assert_eq!(out, vec![
+        "    2: OUT  rb+0",
+        "rb: 5",
+        "rb: 1",
+        "output: 5\n    4: HLT",
+        "pc: 4  rb: 1  inputs: []  outputs: 1\n    4: HLT",
+        "    3: db   0",
+        "error: unknown opcode in 0 at pc 3\n    3: db   0",
+        "error: unknown command `bogus`, try `help`",
+        "error: missing address",
+        "error: 2 words from address 18446744073709551615 run outside of memory",
+        "error: address 18446744073709551615 is outside of memory",
+        "error: at most 1000 words can be shown at once",
+        "error: at most 1000 items can be listed at once",
+        "error: address 18446744073709551615 is outside of memory",
+    ]);
+}
+
+#[test]
+fn reverse_to_the_write_of_an_output() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../d09.in");
+    let program = intcode::parse_program(&std::fs::read_to_string(path).unwrap()).unwrap();
+    let out = session(&program, &["in 1", "c", "out", "rs", "out", "d OUT", "w 64", "rc", "s", "out", "pc 0", "rs"]);
+    assert_eq!(out, vec![
+        "",
+        "output: 2738720997\nhalted\n  903: HLT",
+        "[2738720997]",
+        "  901: OUT  [64]",
+        "[]",
+        "",
+        "",
+        "pc 892 wrote [64]: 2738720996 -> 2738720997\nwatchpoint\n  892: ADD  [64], #1, [64]",
+        "pc 892 wrote [64]: 2738720996 -> 2738720997\n  896: JF   #0, #901",
+        "[]",
+        "    0: MUL  #34463338, #34463338, [63]",
+        "no earlier steps recorded\n    0: MUL  #34463338, #34463338, [63]",
+    ]);
+}
@@ -0,0 +1,82 @@
+use intcode::{asm, disasm, Cpu, State};
+
+#[test]
+fn d09_quine_listing() {
+    let program = intcode::parse_program::<i128>(include_str!("../../d09_t1.in")).unwrap();
+    assert_eq!(disasm::listing(&program), "    \
+    0: ARB  #1
+    2: OUT  rb-1
+    4: ADD  [100], #1, [100]
+    8: EQ   [100], #16, [101]
+   12: JF   [101], #0
+   15: HLT
+");
+}
+
+#[test]
+fn unreached_words_are_data() {
+    // jumps over two data words, and the word after HLT is never reached
+    let program: Vec<i128> = vec![1105, 1, 5, 42, -7, 104, 3, 99, 1002];
+    assert_eq!(disasm::listing(&program), "    \
+    0: JT   #1, #5
+    3: db   42, -7
+    5: OUT  #3
+    7: HLT
+    8: db   1002
+");
+}
+
+fn round_trip(name: &str) {
+    let path = format!("{}/../{}", env!("CARGO_MANIFEST_DIR"), name);
+    let text = std::fs::read_to_string(path).unwrap();
+    let program = intcode::parse_program::<i128>(&text).unwrap();
+    let source = disasm::listing(&program);
+    let assembled = asm::assemble(&source).unwrap();
+    assert_eq!(intcode::format_program(&assembled), text.trim());
+}
+
+#[test]
+fn round_trips() {
+    for name in ["d09_t1.in", "d09_t2.in", "d09_t3.in", "d05.in", "d09.in", "d11.in"] {
+        round_trip(name);
+    }
+}
+
+#[test]
+fn labels_and_directives() {
+    let source = "
+        ; counts down from the input and outputs every value
+        start:  IN   [n]
+        loop:   OUT  [n]
+                ADD  [n], #-1, [n]
+                JT   [n], #loop
+                JF   #0, #end+1   ; skips the padding word
+        end:    db   -5
+                ARB  #table
+                OUT  rb+1
+                HLT
+        n:      ds   1
+        table:  db   $, n, end-start
+    ";
+    let program = asm::assemble(source).unwrap();
+    assert_eq!(program, vec![3, 20, 4, 20, 1001, 20, -1, 20, 1005, 20, 2, 1106, 0, 15, -5,
+                             109, 21, 204, 1, 99, 0, 21, 20, 14]);
+
+    let mut cpu = Cpu::new(&program);
+    cpu.add_input(3);
+    assert_eq!(cpu.run(), Ok(State::Halted));
+    assert_eq!(cpu.outputs, vec![3, 2, 1, 20]);
+}
+
+#[test]
+fn assembler_errors() {
+    let err = |source| asm::assemble(source).unwrap_err().to_string();
+    assert_eq!(err("ADD #1, #2"), "line 1: ADD takes 3 operands, got 2");
+    assert_eq!(err("OUT 5"), "line 1: operand `5` needs a mode: [x], #x or rb+x");
+    assert_eq!(err("HLT\nJT #1, #nowhere"), "line 2: unknown label `nowhere`");
+    assert_eq!(err("HLT\n0: HLT"), "line 2: item is at address 1, not 0");
+    assert_eq!(err("FOO #1"), "line 1: unknown mnemonic `FOO`");
+    assert_eq!(err("HLT\ndb 170141183460469231731687303715884105727+1"),
+               "line 2: `170141183460469231731687303715884105727+1` overflows");
+    assert_eq!(err("ds 18446744073709551615\nds 1"), "line 2: program is too large");
+}
@@ -0,0 +1,56 @@
+use intcode::{Cpu, Error, State};
+
+fn run(program: &[i128]) -> Result<State, Error> {
+    Cpu::new(program).run()
+}
+
+#[test]
+fn unknown_opcode() {
+    assert_eq!(run(&[1, 0, 0, 0, 42]), Err(Error::UnknownOpcode { pc: 4, instr: 42 }));
+    assert_eq!(run(&[-1]), Err(Error::UnknownOpcode { pc: 0, instr: -1 }));
+}
+
+#[test]
+fn unknown_param_mode() {
+    assert_eq!(run(&[301, 0, 0, 0, 99]), Err(Error::UnknownParamMode { pc: 0, instr: 301 }));
+}
+
+#[test]
+fn bad_addresses() {
+    assert_eq!(run(&[1, -3, 0, 0, 99]),
+               Err(Error::NegativeAddress { pc: 0, instr: 1, addr: -3 }));
+    assert_eq!(run(&[1105, 1, -1]),
+               Err(Error::NegativeAddress { pc: 0, instr: 1105, addr: -1 }));
+
+    let mut cpu = Cpu::<i128>::new(&[1, 0, 0, 7, 99]);
+    cpu.set_mem_size(5);
+    assert_eq!(cpu.run(), Err(Error::AddressOutOfRange { pc: 0, instr: 1, addr: 7 }));
+
+    let mut cpu = Cpu::<i128>::new(&[1105, 1, 9]);
+    cpu.set_mem_size(5);
+    assert_eq!(cpu.run(), Err(Error::PcOutOfRange { pc: 9 }));
+
+    // never smaller than the program
+    let mut cpu = Cpu::<i128>::new(&[1101, 2, 3, 5, 99, 0]);
+    cpu.set_mem_size(2);
+    assert_eq!(cpu.mem.limit(), 6);
+    assert_eq!(cpu.run(), Ok(State::Halted));
+}
+
+#[test]
+fn memory_grows_on_demand() {
+    let far = 5_000_000_000;
+    let mut cpu = Cpu::<i128>::new(&[1101, 2, 3, far, 4, far, 99]);
+    assert_eq!(cpu.run(), Ok(State::Halted));
+    assert_eq!(cpu.outputs, vec![5]);
+    assert_eq!(cpu.mem[far as usize], 5);
+    assert_eq!(cpu.mem[far as usize + 1], 0);
+}
+
+#[test]
+fn immediate_write() {
+    let mut cpu = Cpu::<i128>::new(&[103, 0, 99]);
+    cpu.add_input(5);
+    assert_eq!(cpu.run(), Err(Error::ImmediateWrite { pc: 0, instr: 103 }));
+    assert_eq!(cpu.inputs.len(), 1);
+}
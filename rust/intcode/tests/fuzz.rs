@@ -0,0 +1,60 @@
+use intcode::fuzz::{backends, outcome, Backend, Fuzzer};
+use intcode::{Cpu, Error, Overflow};
+
+#[test]
+fn backends_agree() {
+    for seed in [1, 2] {
+        let fuzzer = Fuzzer::new(seed);
+        assert!(fuzzer.run(1000).is_none());
+    }
+    assert_eq!(Fuzzer::new(3).case(17), Fuzzer::new(3).case(17));
+    assert_ne!(Fuzzer::new(3).case(17), Fuzzer::new(4).case(17));
+}
+
+#[test]
+fn mismatches_are_shrunk() {
+    let wrapping = Backend {
+        name: "wrapping cpu",
+        run: |program, inputs, max_steps| Some(outcome(Cpu::with_overflow(program, Overflow::Wrap), inputs, max_steps)),
+    };
+    let mut fuzzer = Fuzzer::with_backends(3, vec![backends()[0], wrapping]);
+    fuzzer.max_steps = 200;
+    let mismatch = fuzzer.run(1000).unwrap();
+    assert_eq!(fuzzer.run(1000).unwrap().shrunk, mismatch.shrunk);
+
+    // a single overflowing instruction is all it takes
+    let program = &mismatch.shrunk.program;
+    assert!(program.len() <= 4);
+    assert!(mismatch.shrunk.inputs.is_empty());
+    assert!(matches!(mismatch.outcomes[0].1.state, Err(Error::Overflow { pc: 0, .. })));
+    assert_ne!(mismatch.outcomes[0].1, mismatch.outcomes[1].1);
+    assert!(mismatch.to_string().starts_with(&format!("backends disagree on case {}\n", mismatch.case)));
+}
+
+#[test]
+fn backends_convert_their_results() {
+    let run = |name: &str, program: &[i128], inputs: &[i128]| {
+        let backend = backends().into_iter().find(|backend| backend.name == name).unwrap();
+        (backend.run)(program, inputs, 100)
+    };
+
+    // squares its input, which overflows an i128 for 2^64
+    let program = intcode::asm::assemble("
+        IN  [x]
+        MUL [x], [x], [x]
+        OUT [x]
+        HLT
+        x: db 0
+    ").unwrap();
+    for input in [-7, 1 << 40, 1 << 64] {
+        let cpu = run("cpu", &program, &[input]).unwrap();
+        assert_eq!(run("bigint cpu", &program, &[input]).as_ref(), Some(&cpu));
+        match run("i64 cpu", &program, &[input]) {
+            Some(narrow) => assert_eq!((input, narrow), (-7, cpu.clone())),
+            None => assert_ne!(input, -7),
+        }
+        let optimized = run("optimized cpu", &program, &[input]).unwrap();
+        assert!(optimized.mem.is_none() && optimized.agrees(&cpu));
+    }
+    assert!(matches!(run("bigint cpu", &program, &[1 << 64]).unwrap().state, Err(Error::Overflow { pc: 2, .. })));
+}
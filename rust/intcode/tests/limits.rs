@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use intcode::limits::{Guard, LimitError, Limits};
+use intcode::{Cpu, State};
+
+fn d09() -> Vec<i128> {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../d09.in");
+    intcode::parse_program(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+// counts up forever, so it never repeats a state
+fn counter() -> Vec<i128> {
+    intcode::asm::assemble("
+        loop: ADD [n], #1, [n]
+              JT  #1, #loop
+        n:    db  0
+    ").unwrap()
+}
+
+#[test]
+fn budgets() {
+    let mut cpu = Cpu::new(&counter());
+    let mut guard = Guard::new(Limits { steps: Some(1000), ..Limits::default() });
+    assert_eq!(guard.run(&mut cpu), Err(LimitError::Steps { pc: 0, steps: 1000 }));
+    assert_eq!(cpu.mem[7], 500);
+    assert_eq!(guard.step(&mut cpu), Err(LimitError::Steps { pc: 0, steps: 1000 }));
+    guard.limits.steps = Some(1001);
+    assert_eq!(guard.step(&mut cpu), Ok(State::Running));
+
+    let mut guard = Guard::new(Limits { time: Some(Duration::from_millis(20)), ..Limits::default() });
+    match guard.run(&mut cpu) {
+        Err(LimitError::Time { elapsed, .. }) => assert!(elapsed >= Duration::from_millis(20)),
+        other => panic!("expected a time out, got {:?}", other),
+    }
+    assert!(guard.elapsed() >= Duration::from_millis(20));
+    assert!(guard.steps() > 0);
+
+    // waiting for input costs nothing, with loop detection or without
+    for detect_loops in [false, true] {
+        let mut cpu = Cpu::<i128>::new(&[3, 0, 99]);
+        let mut guard = Guard::new(Limits { steps: Some(1), detect_loops, ..Limits::default() });
+        assert_eq!(guard.run(&mut cpu), Ok(State::NeedsInput));
+        assert_eq!(guard.step(&mut cpu), Ok(State::NeedsInput));
+        assert_eq!(guard.steps(), 0);
+        cpu.add_input(5);
+        assert_eq!(guard.step(&mut cpu), Ok(State::Running));
+        assert_eq!(guard.step(&mut cpu), Err(LimitError::Steps { pc: 2, steps: 1 }));
+    }
+}
+
+#[test]
+fn loops() {
+    let limits = Limits { steps: Some(100_000), detect_loops: true, ..Limits::default() };
+
+    // flips a flag back and forth
+    let program = intcode::asm::assemble("
+        loop: EQ [f], #0, [f]
+              JT #1, #loop
+        f:    db 0
+    ").unwrap();
+    let mut cpu = Cpu::new(&program);
+    let err = Guard::new(limits).run(&mut cpu).unwrap_err();
+    assert_eq!(err, LimitError::Loop { pc: 0, period: 4 });
+    assert_eq!(err.to_string(), "stuck in a loop of 4 steps without input at pc 0");
+
+    let mut cpu = Cpu::new(&counter());
+    assert!(matches!(Guard::new(limits).run(&mut cpu), Err(LimitError::Steps { .. })));
+
+    // waits for a nonzero input, spinning while it has 0
+    let program = intcode::asm::assemble("
+        loop: IN [x]
+              JF [x], #loop
+        spin: JT #1, #spin
+        x:    db 0
+    ").unwrap();
+    let mut cpu = Cpu::new(&program);
+    let mut guard = Guard::new(limits);
+    cpu.inputs.extend([0, 0, 0]);
+    assert_eq!(guard.run(&mut cpu), Ok(State::NeedsInput));
+    cpu.add_input(1);
+    assert_eq!(guard.run(&mut cpu), Err(LimitError::Loop { pc: 5, period: 1 }));
+
+    // a write the hash does not see looks like a collision, which is caught
+    // by comparing memory
+    let program = intcode::asm::assemble("
+        spin: JT #1, #spin
+        x:    db 0
+    ").unwrap();
+    let mut cpu = Cpu::new(&program);
+    let mut guard = Guard::new(limits);
+    assert_eq!(guard.step(&mut cpu), Ok(State::Running));
+    cpu.mem[3] = 7;
+    assert_eq!(guard.step(&mut cpu), Ok(State::Running));
+    assert_eq!(guard.step(&mut cpu), Err(LimitError::Loop { pc: 0, period: 1 }));
+}
+
+#[test]
+fn real_programs_pass() {
+    let limits = Limits { steps: Some(10_000_000), time: Some(Duration::from_secs(60)), detect_loops: true };
+    let mut cpu = Cpu::new(&d09());
+    cpu.add_input(2);
+    let mut guard = Guard::new(limits);
+    assert_eq!(guard.run(&mut cpu), Ok(State::Halted));
+    assert_eq!(cpu.outputs, vec![50894]);
+    assert!(guard.steps() > 100_000);
+}
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+
+use intcode::optimize::{optimize, Options, Report};
+use intcode::{Cpu, State};
+
+fn load(day: &str) -> Vec<i128> {
+    let path = format!("{}/../{}.in", env!("CARGO_MANIFEST_DIR"), day);
+    intcode::parse_program(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+fn outputs(program: &[i128], input: i128) -> Vec<i128> {
+    let mut cpu = Cpu::new(program);
+    cpu.add_input(input);
+    assert_eq!(cpu.run(), Ok(State::Halted));
+    cpu.outputs
+}
+
+// every output of the hull painting robot, and how many panels it painted
+fn paint(program: &[i128]) -> (Vec<i128>, usize) {
+    let mut cpu = Cpu::new(program);
+    let (mut pos, mut dir) = ((0i32, 0i32), (0, 1));
+    let mut hull = HashMap::new();
+    loop {
+        cpu.add_input(*hull.get(&pos).unwrap_or(&0));
+        if cpu.run() == Ok(State::Halted) {
+            return (cpu.outputs, hull.len());
+        }
+        let n = cpu.outputs.len();
+        hull.insert(pos, cpu.outputs[n - 2]);
+        dir = if cpu.outputs[n - 1] == 0 { (-dir.1, dir.0) } else { (dir.1, -dir.0) };
+        pos = (pos.0 + dir.0, pos.1 + dir.1);
+    }
+}
+
+#[test]
+fn small_rewrites() {
+    let program = intcode::asm::assemble("
+              ADD #2, #3, [x]
+              MUL #4, #5, [x]
+              LT  #1, #2, [y]
+              JT  [y], #a
+              HLT
+        a:    JT  #1, #b
+        b:    JF  #0, #c
+        c:    OUT [x]
+              HLT
+              db  7, 8, 9
+        x:    db  0
+        y:    db  0
+    ").unwrap();
+    let (optimized, report) = optimize(&program, Options::default());
+    assert_eq!(report, Report { folded: 2, dead_stores: 1, threaded: 2, zeroed: 8 });
+    assert_eq!(outputs(&optimized, 0), [20]);
+    assert_eq!(&optimized[..12], [1105, 1, 4, 0, 1101, 20, 0, 28, 1101, 1, 0, 29]);
+    // both jumps now go straight to the output
+    assert_eq!(&optimized[12..15], [1005, 29, 22]);
+    assert_eq!(&optimized[16..22], [0; 6]);
+
+    // a store whose cell is read first stays
+    let program = intcode::asm::assemble("
+        IN  [x]
+        LT  [x], #1, [y]
+        OUT [y]
+        EQ  [x], #2, [y]
+        OUT [y]
+        HLT
+        x: db 0
+        y: db 0
+    ").unwrap();
+    let (optimized, report) = optimize(&program, Options::default());
+    assert_eq!(report, Report::default());
+    assert_eq!(optimized, program[..program.len() - 2]);
+
+    // folding a store that fails would change the instruction it fails in
+    let program = intcode::asm::assemble("
+        LT  #1, #2, [-1]
+        HLT
+    ").unwrap();
+    assert_eq!(optimize(&program, Options::default()), (program, Report::default()));
+}
+
+#[test]
+fn unknown_code_is_left_alone() {
+    // jumps through memory
+    let program = intcode::asm::assemble("
+              JT  #1, [t]
+              OUT #1
+              HLT
+        t:    db  3
+              ADD #1, #1, [t]
+    ").unwrap();
+    assert_eq!(optimize(&program, Options::default()).1, Report::default());
+
+    // modifies its own code
+    let program = intcode::asm::assemble("
+        a:  ADD #1, #1, [x]
+            ADD #2, #0, [a]
+            HLT
+        x:  db 0
+    ").unwrap();
+    assert_eq!(optimize(&program, Options::default()).1, Report::default());
+}
+
+#[test]
+fn puzzle_inputs_behave_the_same() {
+    for options in [Options::default(), Options { compiled: true }] {
+        // patches its first instruction with the input, so stays as it is
+        let program = load("d05");
+        let (optimized, report) = optimize(&program, options);
+        assert_eq!(report, Report::default());
+        for input in [1, 5] {
+            assert_eq!(outputs(&optimized, input), outputs(&program, input));
+        }
+
+        // use relative-mode operands, so only compiled mode changes them,
+        // and then all there is to do is folding
+        let program = load("d09");
+        let (optimized, report) = optimize(&program, options);
+        assert_eq!(report, Report { folded: if options.compiled { 24 } else { 0 }, ..Report::default() });
+        for input in [1, 2] {
+            assert_eq!(outputs(&optimized, input), outputs(&program, input));
+        }
+
+        let program = load("d11");
+        let (optimized, report) = optimize(&program, options);
+        assert_eq!(report, Report { folded: options.compiled as usize, ..Report::default() });
+        let painted = paint(&optimized);
+        assert_eq!(painted.1, 1732);
+        assert_eq!(painted, paint(&program));
+    }
+
+    // what a compiler might emit: a call through a pushed return address
+    let program = intcode::asm::assemble("
+              ARB #100
+              ADD #ret, #0, rb+0
+              MUL #2, #3, [x]
+              ADD #5, #0, [x]
+              JT  #1, #a
+        a:    JT  #1, #f
+              db  7, 8, 9
+        f:    OUT [x]
+              JT  #1, rb+0
+        ret:  HLT
+        x:    db  0
+    ").unwrap();
+    assert_eq!(optimize(&program, Options::default()).1, Report::default());
+    let (optimized, report) = optimize(&program, Options { compiled: true });
+    assert_eq!(report, Report { folded: 0, dead_stores: 1, threaded: 1, zeroed: 6 });
+    assert_eq!(outputs(&optimized, 0), [5]);
+}
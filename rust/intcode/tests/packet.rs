@@ -0,0 +1,98 @@
+use intcode::limits::LimitError;
+use intcode::packet::{Flow, Monitor, Packet, Router, Stop};
+use intcode::threads::NodeError;
+use intcode::{Cpu, Error};
+
+// machine 0 sends (1, 0) to machine 1; every machine forwards (x, y) to the
+// next address as (x + 1, y + its own address)
+fn relay() -> Vec<i128> {
+    intcode::asm::assemble("
+              IN  [addr]
+              JT  [addr], #wait
+              OUT #1
+              OUT #1
+              OUT #0
+        wait: IN  [x]
+              EQ  [x], #-1, [t]
+              JT  [t], #wait
+              IN  [y]
+              ADD [addr], #1, [dest]
+              ADD [x], #1, [x]
+              ADD [y], [addr], [y]
+              OUT [dest]
+              OUT [x]
+              OUT [y]
+              JT  #1, #wait
+        addr: db  0
+        dest: db  0
+        x:    db  0
+        y:    db  0
+        t:    db  0
+    ").unwrap()
+}
+
+#[derive(Default)]
+struct Nat {
+    seen: Vec<Packet>,
+    wakeups: usize,
+    stop_on_packet: bool,
+}
+
+impl Monitor<i128> for Nat {
+    fn unrouted(&mut self, packet: &Packet) -> Flow {
+        self.seen.push(packet.clone());
+        if self.stop_on_packet { Flow::Stop } else { Flow::Continue }
+    }
+
+    fn idle(&mut self, router: &mut Router) -> Flow {
+        if self.wakeups == 2 {
+            return Flow::Stop;
+        }
+        self.wakeups += 1;
+        let last = self.seen.last().unwrap();
+        router.send(0, last.x, last.y);
+        Flow::Continue
+    }
+}
+
+#[test]
+fn relay_around_fifty_machines() {
+    let mut router = Router::with_program(&relay(), 50);
+    let mut nat = Nat { stop_on_packet: true, ..Nat::default() };
+    assert_eq!(router.run(&mut nat), Ok(Stop::Monitor));
+    assert_eq!(nat.seen, vec![Packet { from: 49, dest: 50, x: 50, y: 1225 }]);
+    assert_eq!(router.rounds, 0);
+}
+
+#[test]
+fn idle_network_is_woken_by_monitor() {
+    let mut router = Router::with_program(&relay(), 50);
+    let mut nat = Nat::default();
+    assert_eq!(router.run(&mut nat), Ok(Stop::Idle));
+    let ys: Vec<i128> = nat.seen.iter().map(|p| p.y).collect();
+    assert_eq!(ys, vec![1225, 2450, 3675]);
+    assert_eq!(nat.seen[2].x, 150);
+    assert!((0..50).all(|addr| router.queued(addr) == 0));
+
+    let mut router = Router::with_program(&relay(), 3);
+    assert_eq!(router.run(()), Ok(Stop::Idle));
+    assert_eq!(router.rounds, 3);
+}
+
+#[test]
+fn halts_and_errors() {
+    let mut router = Router::<i128>::with_program(&[3, 5, 99], 4);
+    assert_eq!(router.run(()), Ok(Stop::Halted));
+
+    let broken = vec![Cpu::new(&[3, 5, 99]), Cpu::new(&[3, 5, 98])];
+    let err = Router::<i128>::new(broken).run(()).err().unwrap();
+    assert_eq!(err, NodeError { node: 1, error: LimitError::Machine(Error::UnknownOpcode { pc: 2, instr: 98 }) });
+
+    // the second machine spins without ever reading
+    let spinning = vec![Cpu::new(&[3, 5, 99]), Cpu::new(&[3, 5, 1105, 1, 2])];
+    let mut router = Router::<i128>::new(spinning);
+    router.max_steps = Some(1000);
+    let err = router.run(()).err().unwrap();
+    assert_eq!(err, NodeError { node: 1, error: LimitError::Steps { pc: 2, steps: 1000 } });
+    assert_eq!(err.to_string(), "machine 1: step budget of 1000 used up at pc 2");
+}
@@ -0,0 +1,55 @@
+use intcode::profile::Profiler;
+use intcode::{Cpu, Op, State};
+
+#[test]
+fn counts_and_coverage() {
+    // counts x down from 3, never taking the error branch
+    let program = intcode::asm::assemble("
+        loop: ADD [x], #-1, [x]
+              JT  [x], #loop
+              JT  [x], #bad
+              HLT
+        bad:  OUT #-1
+              HLT
+        x:    db  3
+    ").unwrap();
+    let mut cpu = Cpu::new(&program);
+    let mut profiler = Profiler::new();
+    assert_eq!(cpu.run_with(&mut profiler), Ok(State::Halted));
+    assert_eq!((profiler.count(0), profiler.count(4), profiler.count(7), profiler.count(10)), (3, 3, 1, 1));
+    assert_eq!((profiler.op_count(Op::Add), profiler.op_count(Op::Output)), (3, 0));
+    assert_eq!(profiler.total(), 8);
+    assert_eq!(profiler.hottest(2), vec![(0, 3), (4, 3)]);
+    assert_eq!(profiler.unreached(&program), vec![11, 13]);
+    let report = profiler.report(&program);
+    assert_eq!(report.lines().collect::<Vec<_>>(), vec![
+        "         3     0: ADD  [14], #-1, [14]",
+        "         3     4: JT   [14], #0",
+        "         1     7: JT   [14], #11",
+        "         1    10: HLT",
+        "         -    11: OUT  #-1",
+        "         -    13: HLT",
+        "              14: db   3",
+        "",
+        "coverage: 4 of 6 instructions (66.7%), 8 steps",
+        "ADD           3",
+        "JT            4",
+        "HLT           1",
+    ]);
+}
+
+#[test]
+fn d09_follows_indirect_jumps() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../d09.in");
+    let program: Vec<i128> = intcode::parse_program(&std::fs::read_to_string(path).unwrap()).unwrap();
+    let mut cpu = Cpu::new(&program);
+    cpu.add_input(2);
+    let mut profiler = Profiler::new();
+    assert_eq!(cpu.run_with(&mut profiler), Ok(State::Halted));
+    assert_eq!(cpu.outputs, vec![50894]);
+    assert_eq!(profiler.total(), 371206);
+    // reached only through `JF #0, rb+0`
+    let report = profiler.report(&program);
+    assert!(report.contains("     18559   957: ADD  rb+1, rb-1, rb-2\n"));
+    assert!(!report.contains("executed outside the listing"));
+}
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::fs;
+
+use intcode::{Cpu, State};
+
+fn load(name: &str) -> Vec<i128> {
+    let path = format!("{}/../{}", env!("CARGO_MANIFEST_DIR"), name);
+    let text = fs::read_to_string(&path).expect("failed to read program");
+    intcode::parse_program(&text).expect("failed to parse number")
+}
+
+fn run_with_inputs(program: &[i128], inputs: &[i128]) -> Vec<i128> {
+    let mut cpu = Cpu::new(program);
+    for &x in inputs {
+        cpu.add_input(x);
+    }
+    cpu.run().unwrap();
+    cpu.outputs
+}
+
+fn permutations(elems: &[i128]) -> Vec<Vec<i128>> {
+    if elems.len() <= 1 {
+        return vec![elems.to_vec()];
+    }
+    let mut perms = Vec::new();
+    for i in 0..elems.len() {
+        let mut rest = elems.to_vec();
+        let first = rest.remove(i);
+        for mut perm in permutations(&rest) {
+            perm.insert(0, first);
+            perms.push(perm);
+        }
+    }
+    perms
+}
+
+#[test]
+fn d09_quine() {
+    let program = load("d09_t1.in");
+    assert_eq!(run_with_inputs(&program, &[]), program);
+}
+
+#[test]
+fn d09_large_product() {
+    let program = load("d09_t2.in");
+    let outputs = run_with_inputs(&program, &[]);
+    assert_eq!(outputs, vec![34915192 * 34915192]);
+    assert_eq!(outputs[0].to_string().len(), 16);
+}
+
+#[test]
+fn d09_large_immediate() {
+    let program = load("d09_t3.in");
+    assert_eq!(run_with_inputs(&program, &[]), vec![1125899906842624]);
+}
+
+#[test]
+fn d02_puzzle() {
+    let program = load("d02.in");
+    let output = |noun, verb| {
+        let mut cpu = Cpu::new(&program);
+        cpu.mem[1] = noun;
+        cpu.mem[2] = verb;
+        cpu.run().unwrap();
+        cpu.mem[0]
+    };
+    assert_eq!(output(12, 2), 3224742);
+    assert_eq!(output(79, 60), 19690720);
+}
+
+#[test]
+fn d05_puzzle() {
+    let program = load("d05.in");
+    assert_eq!(run_with_inputs(&program, &[1]).last(), Some(&4887191));
+    assert_eq!(run_with_inputs(&program, &[5]), vec![3419022]);
+}
+
+#[test]
+fn d07_puzzle() {
+    let program = load("d07.in");
+
+    let best = permutations(&[0, 1, 2, 3, 4])
+        .iter()
+        .map(|phases| {
+            phases.iter().fold(0, |signal, &phase| {
+                run_with_inputs(&program, &[phase, signal])[0]
+            })
+        })
+        .max();
+    assert_eq!(best, Some(46014));
+
+    let best = permutations(&[5, 6, 7, 8, 9])
+        .iter()
+        .map(|phases| {
+            let mut amps: Vec<Cpu> = phases
+                .iter()
+                .map(|&phase| {
+                    let mut cpu = Cpu::new(&program);
+                    cpu.add_input(phase);
+                    cpu
+                })
+                .collect();
+            let mut signal = 0;
+            loop {
+                for amp in amps.iter_mut() {
+                    amp.add_input(signal);
+                    match amp.run_until_output().unwrap() {
+                        State::Output(x) => signal = x,
+                        _ => return signal,
+                    }
+                }
+            }
+        })
+        .max();
+    assert_eq!(best, Some(19581200));
+}
+
+#[test]
+fn d09_puzzle() {
+    let program = load("d09.in");
+    assert_eq!(run_with_inputs(&program, &[1]), vec![2738720997]);
+    assert_eq!(run_with_inputs(&program, &[2]), vec![50894]);
+}
+
+fn paint(program: &[i128], start: i128) -> HashMap<(i32, i32), i128> {
+    let mut cpu = Cpu::new(program);
+    let mut panels = HashMap::new();
+    let (mut pos, mut dir) = ((0, 0), (0, 1));
+    panels.insert(pos, start);
+    loop {
+        cpu.add_input(*panels.get(&pos).unwrap_or(&0));
+        if cpu.run().unwrap() == State::Halted {
+            break panels;
+        }
+        let turn = cpu.outputs.pop().unwrap();
+        let color = cpu.outputs.pop().unwrap();
+        panels.insert(pos, color);
+        dir = if turn == 0 { (-dir.1, dir.0) } else { (dir.1, -dir.0) };
+        pos = (pos.0 + dir.0, pos.1 + dir.1);
+    }
+}
+
+#[test]
+fn d11_puzzle() {
+    let program = load("d11.in");
+    assert_eq!(paint(&program, 0).len(), 1732);
+
+    let white = paint(&program, 1).values().filter(|&&c| c == 1).count();
+    assert_eq!(white, 94);
+}
+
+#[test]
+fn run_states() {
+    let mut cpu = Cpu::<i128>::new(&[3, 9, 4, 9, 1105, 1, 0, 99, 0, 0]);
+    assert_eq!(cpu.run().unwrap(), State::NeedsInput);
+    cpu.add_input(7);
+    assert_eq!(cpu.run_until_output().unwrap(), State::Output(7));
+    assert_eq!(cpu.run_for(100).unwrap(), State::NeedsInput);
+    assert!(cpu.outputs.is_empty());
+
+    let mut cpu = Cpu::<i128>::new(&[1105, 1, 0]);
+    assert_eq!(cpu.run_for(100).unwrap(), State::StepLimitReached);
+}
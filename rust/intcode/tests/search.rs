@@ -0,0 +1,97 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use intcode::aot::Machine;
+use intcode::search::Search;
+use intcode::Cpu;
+
+fn d02() -> Vec<i128> {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../d02.in");
+    intcode::parse_program(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+// outputs in0 * in1
+fn product() -> Vec<i128> {
+    intcode::asm::assemble("
+        IN  [a]
+        IN  [b]
+        MUL [a], [b], [a]
+        OUT [a]
+        HLT
+        a: db 0
+        b: db 0
+    ").unwrap()
+}
+
+#[test]
+fn noun_and_verb() {
+    let program = d02();
+    for threads in [1, 4] {
+        let init = Machine::interpreted(&program);
+        let mut search = Search::new(&init);
+        search.mem(1, 0..=99).mem(2, 0..=99);
+        search.threads = threads;
+        assert_eq!(search.find(|m| m.mem[0] == 19690720), Some(vec![79, 60]));
+        assert_eq!(search.find(|m| m.mem[0] == -1), None);
+    }
+
+    let init = Cpu::new(&program);
+    let mut search = Search::new(&init);
+    search.mem(1, 12..=12).mem(2, 0..=99);
+    assert_eq!(search.find_all(|cpu| cpu.mem[0] < 3224743), vec![vec![12, 0], vec![12, 1], vec![12, 2]]);
+}
+
+#[test]
+fn inputs_and_pruning() {
+    let calls = AtomicUsize::new(0);
+    let program = product();
+    let init = Cpu::new(&program);
+    let mut search = Search::new(&init);
+    search.input(1..=12).input(1..=12);
+    let twelve = |cpu: &Cpu| cpu.outputs == [12];
+    assert_eq!(search.find_all(twelve).len(), 6);
+    assert_eq!(search.find(twelve), Some(vec![1, 12]));
+
+    search.prune(|values| {
+        calls.fetch_add(1, Ordering::Relaxed);
+        values[0] < 3 || (values.len() == 2 && values[1] < values[0])
+    });
+    assert_eq!(search.find_all(twelve), vec![vec![3, 4]]);
+    assert!(calls.load(Ordering::Relaxed) < 2 * 144);
+
+    let mut search = Search::new(&init);
+    search.known_input(5).input(-3..=3);
+    assert_eq!(search.combinations(), Some(7));
+    assert_eq!(search.find_all(|cpu| cpu.outputs[0] < 0), vec![vec![-3], vec![-2], vec![-1]]);
+
+    // too many combinations to count, let alone run
+    let mut search = Search::new(&init);
+    search.input(i128::MIN..=i128::MAX).input(1..=2);
+    assert_eq!(search.combinations(), None);
+    assert_eq!(search.find(|_| true), None);
+    assert!(search.find_all(|_| true).is_empty());
+    let mut search = Search::new(&init);
+    search.input(0..=u32::MAX as i128).input(0..=u32::MAX as i128).input(0..=1);
+    assert_eq!(search.combinations(), None);
+}
+
+#[test]
+fn failed_runs_never_match() {
+    // spins on 0, fails on negative inputs and outputs anything else
+    let program = intcode::asm::assemble("
+              IN  [x]
+              JF  [x], #spin
+              LT  [x], #0, [t]
+              JT  [t], #-1
+              OUT [x]
+              HLT
+        spin: JT  #1, #spin
+        x:    db  0
+        t:    db  0
+    ").unwrap();
+    let init = Cpu::new(&program);
+    let mut search = Search::new(&init);
+    search.input(-2..=3);
+    search.max_steps = 1000;
+    assert_eq!(search.find_all(|_| true), vec![vec![1], vec![2], vec![3]]);
+    assert_eq!(search.find(|cpu| cpu.outputs[0] > 1), Some(vec![2]));
+}
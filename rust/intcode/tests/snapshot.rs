@@ -0,0 +1,65 @@
+use intcode::{Cpu, Overflow, State};
+
+fn d09() -> Vec<i128> {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../d09.in");
+    intcode::parse_program(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+#[test]
+fn forks_do_not_share_writes() {
+    let mut cpu = Cpu::<i128>::new(&[1, 0, 0, 0, 99]);
+    let fork = cpu.snapshot();
+    cpu.mem[3000] = 7;
+    assert_eq!(cpu.run(), Ok(State::Halted));
+    assert_eq!((cpu.mem[0], cpu.mem[3000]), (2, 7));
+    assert_eq!((fork.mem[0], fork.mem[3000]), (1, 0));
+
+    cpu.restore(&fork);
+    assert_eq!((cpu.pc, cpu.mem[0], cpu.mem[3000]), (0, 1, 0));
+}
+
+#[test]
+fn saved_state_resumes_mid_run() {
+    let mut cpu = Cpu::new(&d09());
+    cpu.add_input(2);
+    assert_eq!(cpu.run_for(10_000), Ok(State::StepLimitReached));
+
+    let mut file = Vec::new();
+    cpu.save_state(&mut file).unwrap();
+    let mut loaded = Cpu::<i128>::load_state(&file[..]).unwrap();
+    assert_eq!((loaded.pc, loaded.base_offset), (cpu.pc, cpu.base_offset));
+    assert_eq!(loaded.mem.extent(), cpu.mem.extent());
+    assert_eq!(loaded.mem.read(0, 2000), cpu.mem.read(0, 2000));
+
+    assert_eq!(loaded.run(), Ok(State::Halted));
+    assert_eq!(loaded.outputs, vec![50894]);
+}
+
+#[test]
+fn state_file_format() {
+    let mut cpu = Cpu::<i128>::with_overflow(&[3, 9, 4, 9, 99, 0, 0, 0, 0, 0], Overflow::Wrap);
+    cpu.set_mem_size(100);
+    cpu.inputs.extend([5, -6]);
+    assert_eq!(cpu.step(), Ok(State::Running));
+    let mut file = Vec::new();
+    cpu.save_state(&mut file).unwrap();
+    assert_eq!(String::from_utf8(file.clone()).unwrap(), "\
+intcode-state 1
+pc 2
+rb 0
+overflow wrap
+limit 100
+inputs -6
+outputs
+extent 10
+mem 0 3,9,4,9,99,0,0,0,0,5
+");
+    let loaded = Cpu::<i128>::load_state(&file[..]).unwrap();
+    assert_eq!((loaded.overflow(), loaded.mem.limit()), (Overflow::Wrap, 100));
+    assert!(Cpu::<i128>::load_state(&b"intcode-state 1\npc 0\n"[..]).is_err());
+
+    let corrupt = String::from_utf8(file).unwrap().replace("mem 0 ", "mem 18446744073709551610 ");
+    let err = Cpu::<i128>::load_state(corrupt.as_bytes()).err().unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(err.to_string(), "words at 18446744073709551610 run past the end of memory");
+}
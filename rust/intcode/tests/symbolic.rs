@@ -0,0 +1,104 @@
+use intcode::symbolic::{Expr, Goal, Method, Problem, SymError, Symbol, SymbolicCpu};
+use intcode::State;
+
+fn d02() -> Vec<i128> {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../d02.in");
+    intcode::parse_program(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+#[test]
+fn d02_is_linear() {
+    let program = d02();
+    let mut problem = Problem::new(&program);
+    problem.mem(1, 0..=99).mem(2, 0..=99);
+
+    let linear = problem.expr(Goal::Mem(0)).unwrap().linear().unwrap();
+    assert_eq!(linear.coeffs.len(), 2);
+    assert_eq!(linear.coeffs[&Symbol::Mem(2)], 1);
+
+    let solution = problem.solve(Goal::Mem(0), 19690720).unwrap();
+    assert_eq!(solution.method, Method::Linear);
+    assert_eq!(solution.values, vec![79, 60]);
+    assert_eq!(problem.solve(Goal::Mem(0), 3224742).unwrap().values, vec![12, 2]);
+    assert_eq!(problem.solve(Goal::Mem(0), -1), None);
+}
+
+#[test]
+fn non_linear_goal_is_searched() {
+    // outputs in0 * in1 + 1
+    let program = intcode::asm::assemble("
+        IN  [a]
+        IN  [b]
+        MUL [a], [b], [a]
+        ADD [a], #1, [a]
+        OUT [a]
+        HLT
+        a: db 0
+        b: db 0
+    ").unwrap();
+    let mut cpu = SymbolicCpu::new(&program);
+    cpu.inputs.extend([Expr::Sym(Symbol::Input(0)), Expr::Const(3)]);
+    assert_eq!(cpu.run_for(100), Ok(State::Halted));
+    assert_eq!(cpu.outputs[0].to_string(), "(in0 * 3 + 1)");
+    assert_eq!(cpu.outputs[0].linear().unwrap().coeffs[&Symbol::Input(0)], 3);
+
+    let mut problem = Problem::new(&program);
+    problem.input(1..=10).input(1..=10);
+    assert_eq!(problem.expr(Goal::Output(0)).unwrap().to_string(), "(in0 * in1 + 1)");
+    let solution = problem.solve(Goal::Output(0), 43).unwrap();
+    assert_eq!(solution.method, Method::Expression);
+    assert_eq!(solution.values, vec![6, 7]);
+
+    let mut problem = Problem::new(&program);
+    problem.known_input(5).input(0..=100);
+    let solution = problem.solve(Goal::Output(0), 406).unwrap();
+    assert_eq!((solution.method, solution.values), (Method::Linear, vec![81]));
+
+    // linear goals are solved whatever the bounds, but there is no
+    // searching them
+    let mut problem = Problem::new(&program);
+    problem.known_input(-3).input(i128::MIN..=i128::MAX);
+    assert_eq!(problem.solve(Goal::Output(0), -41).unwrap().values, vec![14]);
+    let mut problem = Problem::new(&program);
+    problem.input(i128::MIN..=i128::MAX).input(1..=10);
+    assert_eq!(problem.solve(Goal::Output(0), 43), None);
+}
+
+#[test]
+fn symbolic_branches_fall_back_to_running() {
+    // outputs its input doubled if it is below 10, else unchanged
+    let program = intcode::asm::assemble("
+              IN  [x]
+              LT  [x], #10, [t]
+              JF  [t], #out
+              MUL [x], #2, [x]
+        out:  OUT [x]
+              HLT
+        x:    db  0
+        t:    db  0
+    ").unwrap();
+    let mut problem = Problem::new(&program);
+    problem.input(0..=50);
+    let err = problem.expr(Goal::Output(0)).unwrap_err();
+    assert_eq!(err, SymError::SymbolicBranch { pc: 6 });
+    assert_eq!(err.to_string(), "branch on a symbolic value at pc 6");
+
+    let solution = problem.solve(Goal::Output(0), 12).unwrap();
+    assert_eq!((solution.method, solution.values), (Method::Execution, vec![6]));
+    assert_eq!(problem.solve(Goal::Output(0), 19).unwrap().values, vec![19]);
+    assert_eq!(problem.solve(Goal::Output(0), 9), None);
+
+    // outputs its input plus one, then spins forever
+    let program = intcode::asm::assemble("
+              IN  [x]
+              ADD [x], #1, [x]
+              OUT [x]
+        spin: JT  #1, #spin
+        x:    db  0
+    ").unwrap();
+    let mut problem = Problem::new(&program);
+    problem.input(0..=10);
+    problem.max_steps = 100;
+    assert_eq!(problem.expr(Goal::Output(0)), Err(SymError::StepLimitReached { pc: 8 }));
+    assert_eq!(problem.solve(Goal::Output(0), 6), None);
+}
@@ -0,0 +1,110 @@
+use std::collections::VecDeque;
+
+use intcode::task::{self, Executor};
+use intcode::{Cpu, State};
+
+fn d07() -> Vec<i128> {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../d07.in");
+    intcode::parse_program(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+// doubles its input until it reads 0
+fn doubler() -> Vec<i128> {
+    intcode::asm::assemble("
+        loop: IN  [x]
+              JF  [x], #done
+              MUL [x], #2, [x]
+              OUT [x]
+              JT  #1, #loop
+        done: HLT
+        x:    db  0
+    ").unwrap()
+}
+
+#[test]
+fn block_on_queues() {
+    let mut cpu = Cpu::new(&doubler());
+    let mut input = VecDeque::from(vec![1, 5]);
+    let mut output = Vec::new();
+    let state = task::block_on(cpu.run_async(&mut input, &mut output)).unwrap();
+    assert_eq!(state, Ok(State::NeedsInput));
+    assert_eq!(output, vec![2, 10]);
+
+    input.push_back(0);
+    let state = task::block_on(cpu.run_async(&mut input, &mut output)).unwrap();
+    assert_eq!(state, Ok(State::Halted));
+}
+
+#[test]
+fn feedback_ring() {
+    let program = d07();
+    let phases = [9, 8, 7, 6, 5];
+    let (senders, mut receivers): (Vec<_>, Vec<_>) = phases.iter().map(|_| task::channel()).unzip();
+    senders[0].send(0);
+
+    let mut executor = Executor::new();
+    let mut first = None;
+    for (i, &phase) in phases.iter().enumerate().rev() {
+        let mut cpu = Cpu::new(&program);
+        cpu.add_input(phase);
+        let mut input = receivers.pop().unwrap();
+        let output = senders[(i + 1) % phases.len()].clone();
+        first = Some(executor.spawn(async move {
+            let state = cpu.run_async(&mut input, output).await;
+            (state, input)
+        }));
+    }
+    drop(senders);
+    assert_eq!(executor.run(), 0);
+
+    // the last signal is left unread in the first amplifier's input
+    let (state, input) = first.unwrap().take().unwrap();
+    assert_eq!(state, Ok(State::Halted));
+    let mut signal = None;
+    while let Some(x) = input.try_recv() {
+        signal = Some(x);
+    }
+
+    let mut amps: Vec<Cpu> = phases.iter()
+        .map(|&phase| {
+            let mut cpu = Cpu::new(&program);
+            cpu.add_input(phase);
+            cpu
+        })
+        .collect();
+    let mut expected = 0;
+    'run: loop {
+        for amp in amps.iter_mut() {
+            amp.add_input(expected);
+            match amp.run_until_output().unwrap() {
+                State::Output(x) => expected = x,
+                _ => break 'run,
+            }
+        }
+    }
+    assert_eq!(signal, Some(expected));
+}
+
+#[test]
+fn many_machines_and_stalls() {
+    // a hundred doublers in a chain on one thread
+    let (first, mut input) = task::channel();
+    let mut executor = Executor::new();
+    for _ in 0..100 {
+        let (sender, receiver) = task::channel();
+        let mut cpu = Cpu::new(&doubler());
+        executor.spawn(async move { cpu.run_async(input, sender).await });
+        input = receiver;
+    }
+    first.send(1);
+    assert_eq!(executor.run(), 100);
+    assert_eq!(input.try_recv(), Some(1 << 100));
+
+    // closing the first input lets every machine run dry
+    drop(first);
+    assert_eq!(executor.run(), 0);
+
+    let mut stuck = Cpu::new(&doubler());
+    let (_sender, receiver) = task::channel();
+    assert_eq!(task::block_on(stuck.run_async(receiver, Vec::new())), None);
+}
@@ -0,0 +1,95 @@
+use intcode::limits::LimitError;
+use intcode::threads::{self, Network, NodeError};
+use intcode::{Cpu, Error, State};
+
+fn d07() -> Vec<i128> {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../d07.in");
+    intcode::parse_program(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+// d07's feedback loop run by hand, one amplifier at a time
+fn feedback(program: &[i128], phases: &[i128]) -> i128 {
+    let mut amps = amps(program, phases);
+    let mut signal = 0;
+    loop {
+        for amp in amps.iter_mut() {
+            amp.add_input(signal);
+            match amp.run_until_output().unwrap() {
+                State::Output(x) => signal = x,
+                _ => return signal,
+            }
+        }
+    }
+}
+
+fn amps(program: &[i128], phases: &[i128]) -> Vec<Cpu> {
+    phases.iter()
+        .map(|&phase| {
+            let mut cpu = Cpu::new(program);
+            cpu.add_input(phase);
+            cpu
+        })
+        .collect()
+}
+
+#[test]
+fn spawned_machine_echoes() {
+    // doubles its input until it reads 0
+    let program = intcode::asm::assemble("
+        loop: IN  [x]
+              JF  [x], #done
+              MUL [x], #2, [x]
+              OUT [x]
+              JT  #1, #loop
+        done: HLT
+        x:    db  0
+    ").unwrap();
+    let handle = threads::spawn(Cpu::new(&program));
+    handle.input.send(21).unwrap();
+    assert_eq!(handle.output.recv(), Ok(42));
+    handle.input.send(0).unwrap();
+    assert!(handle.join().is_ok());
+}
+
+#[test]
+fn pipeline_and_ring() {
+    let program = d07();
+    let mut machines = amps(&program, &[4, 3, 2, 1, 0]);
+    machines[0].add_input(0);
+    let outcome = Network::pipeline(machines).run().unwrap();
+    assert!(!outcome.quiesced);
+    assert_eq!(outcome.outputs[4], vec![feedback(&program, &[4, 3, 2, 1, 0])]);
+
+    let mut machines = amps(&program, &[9, 8, 7, 6, 5]);
+    machines[0].add_input(0);
+    let outcome = Network::ring(machines).run().unwrap();
+    assert!(!outcome.quiesced);
+    assert!(outcome.outputs[4].len() > 1);
+    assert_eq!(*outcome.outputs[4].last().unwrap(), feedback(&program, &[9, 8, 7, 6, 5]));
+}
+
+#[test]
+fn quiescence_and_errors() {
+    // forwards its first input, then keeps reading
+    let forward: Vec<i128> = vec![3, 9, 4, 9, 3, 9, 1105, 1, 4, 0];
+    let mut machines = vec![Cpu::new(&forward), Cpu::new(&forward)];
+    machines[0].add_input(7);
+    let mut network = Network::new(machines);
+    network.connect(0, 1).connect(0, 0);
+    let outcome = network.run().unwrap();
+    assert!(outcome.quiesced);
+    assert_eq!(outcome.outputs, vec![vec![7], vec![7]]);
+    assert_eq!(outcome.machines[1].pc, 4);
+
+    let broken = vec![Cpu::<i128>::new(&[4, 5, 98, 0, 0, 6]), Cpu::new(&[3, 0, 99])];
+    let err = Network::pipeline(broken).run().err().unwrap();
+    assert_eq!(err, NodeError { node: 0, error: LimitError::Machine(Error::UnknownOpcode { pc: 2, instr: 98 }) });
+    assert_eq!(err.to_string(), "machine 0: unknown opcode in 98 at pc 2");
+
+    // the first machine outputs once, then spins without reading
+    let spinning = vec![Cpu::<i128>::new(&[104, 7, 1105, 1, 2]), Cpu::new(&forward)];
+    let mut network = Network::pipeline(spinning);
+    network.max_steps = Some(1000);
+    let err = network.run().err().unwrap();
+    assert_eq!(err, NodeError { node: 0, error: LimitError::Steps { pc: 2, steps: 1000 } });
+}
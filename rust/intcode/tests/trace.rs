@@ -0,0 +1,67 @@
+use intcode::trace::{self, Step, Tracer};
+use intcode::{Cpu, Op, State};
+
+fn d09() -> Vec<i128> {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../d09.in");
+    intcode::parse_program(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+#[test]
+fn records_operands_inputs_and_outputs() {
+    let program = intcode::asm::assemble("
+        IN  [x]
+        ADD [x], #1, [x]
+        OUT [x]
+        HLT
+        x: db 0
+    ").unwrap();
+    let mut cpu = Cpu::new(&program);
+    cpu.add_input(41);
+    let mut tracer = Tracer::new();
+    assert_eq!(tracer.run(&mut cpu), Ok(State::Halted));
+    let lines: Vec<String> = tracer.steps.iter().map(|s| s.to_string()).collect();
+    assert_eq!(lines, ["0 IN w9=41 i41", "2 ADD r9=41 w9=42", "6 OUT r9=42 o42", "8 HLT"]);
+    assert_eq!(cpu.outputs, vec![42]);
+}
+
+#[test]
+fn save_load_and_replay() {
+    let program = d09();
+    let mut cpu = Cpu::new(&program);
+    cpu.add_input(1);
+    let mut tracer = Tracer::new();
+    assert_eq!(tracer.run(&mut cpu), Ok(State::Halted));
+    assert_eq!(cpu.outputs, vec![2738720997]);
+
+    let mut file = Vec::new();
+    tracer.save(&mut file).unwrap();
+    let loaded = Tracer::<i128>::load(&file[..]).unwrap();
+    assert_eq!(loaded.steps, tracer.steps);
+    assert_eq!(trace::replay(&program, &loaded.steps), Ok(tracer.steps.len()));
+}
+
+#[test]
+fn replay_reports_first_divergence() {
+    let program = d09();
+    let mut cpu = Cpu::new(&program);
+    cpu.add_input(1);
+    let mut tracer = Tracer::new();
+    tracer.run(&mut cpu).unwrap();
+
+    let mut patched = program.clone();
+    let last = tracer.steps.iter().rposition(|s| s.op == Op::Output).unwrap();
+    let Step { pc, .. } = tracer.steps[last];
+    // turn the final OUT into a HLT
+    patched[pc] = 99;
+    let divergence = trace::replay(&patched, &tracer.steps).unwrap_err();
+    assert_eq!(divergence.index, last);
+    assert_eq!(divergence.actual.unwrap().op, Op::Halt);
+    assert!(Tracer::<i128>::load(&b"not a trace\n"[..]).is_err());
+
+    let mut file = Vec::new();
+    tracer.save(&mut file).unwrap();
+    file.extend("0 OUT \u{e9}5\n".as_bytes());
+    let err = Tracer::<i128>::load(&file[..]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(err.to_string().ends_with(": bad trace step `0 OUT \u{e9}5`"));
+}
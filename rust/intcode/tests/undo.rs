@@ -0,0 +1,45 @@
+use intcode::undo::History;
+use intcode::{Cpu, State};
+
+#[test]
+fn undoing_every_step_restores_the_start() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../d09.in");
+    let program: Vec<i128> = intcode::parse_program(&std::fs::read_to_string(path).unwrap()).unwrap();
+    let mut cpu = Cpu::new(&program);
+    cpu.add_input(1);
+    let mut history = History::new();
+    loop {
+        match history.step(&mut cpu, ()).unwrap() {
+            State::Running => (),
+            State::Output(x) => cpu.outputs.push(x),
+            state => {
+                assert_eq!(state, State::Halted);
+                break;
+            }
+        }
+    }
+    assert_eq!(cpu.outputs, vec![2738720997]);
+    assert!(!history.is_empty());
+
+    while history.undo(&mut cpu).is_some() {}
+    assert_eq!((cpu.pc, cpu.base_offset), (0, 0));
+    assert_eq!(cpu.inputs, vec![1]);
+    assert!(cpu.outputs.is_empty());
+    let mut expected = program.clone();
+    expected.resize(cpu.mem.extent(), 0);
+    assert_eq!(cpu.mem.read(0, cpu.mem.extent()), expected);
+}
+
+#[test]
+fn history_forgets_the_oldest_steps() {
+    let mut cpu = Cpu::<i128>::new(&[1001, 5, 1, 5, 99, 0]);
+    let mut history = History::new();
+    history.limit = 1;
+    history.step(&mut cpu, ()).unwrap();
+    history.step(&mut cpu, ()).unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(cpu.mem[5], 1);
+    assert!(history.undo(&mut cpu).is_some());
+    assert_eq!((cpu.pc, cpu.mem[5]), (0, 0));
+    assert!(history.undo(&mut cpu).is_none());
+}
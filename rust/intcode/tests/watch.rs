@@ -0,0 +1,46 @@
+use intcode::watch::{Event, Watcher};
+use intcode::{Cpu, State};
+
+fn run_watched(cpu: &mut Cpu, watcher: &mut Watcher<i128>) -> State {
+    loop {
+        match cpu.step_with(watcher).unwrap() {
+            State::Running | State::Output(_) => continue,
+            state => return state,
+        }
+    }
+}
+
+#[test]
+fn read_and_write_watchpoints() {
+    let program = intcode::asm::assemble("
+        IN  [x]
+        ADD [x], [y], [y]
+        OUT [y]
+        HLT
+        x: db 0
+        y: db 10
+    ").unwrap();
+    let mut cpu = Cpu::new(&program);
+    cpu.add_input(5);
+    let mut watcher = Watcher::new();
+    watcher.watch(9..10, true, false);
+    watcher.watch(10..11, false, true);
+    assert_eq!(run_watched(&mut cpu, &mut watcher), State::Halted);
+    assert_eq!(watcher.events, vec![
+        Event::Read { pc: 2, addr: 9, value: 5 },
+        Event::Write { pc: 2, addr: 10, old: 10, new: 15 },
+    ]);
+}
+
+#[test]
+fn d11_rewrites_its_own_code() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../d11.in");
+    let program = intcode::parse_program(&std::fs::read_to_string(path).unwrap()).unwrap();
+    let mut cpu = Cpu::new(&program);
+    cpu.inputs.extend([0, 0]);
+    let mut watcher = Watcher::new();
+    watcher.detect_code_writes = true;
+    assert_eq!(run_watched(&mut cpu, &mut watcher), State::NeedsInput);
+    assert_eq!(watcher.events[0], Event::CodeWrite { pc: 33, addr: 29, old: 1, new: 0 });
+    assert_eq!(watcher.events[0].to_string(), "pc 33 modified executed code at [29]: 1 -> 0");
+}
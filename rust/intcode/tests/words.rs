@@ -0,0 +1,58 @@
+use intcode::{BigInt, Cpu, Error, Overflow, State, Word};
+
+// squares its input three times and outputs the result
+const SQUARE_3X: &str = "3,0,2,0,0,0,2,0,0,0,2,0,0,0,4,0,99";
+
+fn square_3x<W: Word>(input: W, overflow: Overflow) -> Result<State<W>, Error<W>> {
+    let program = intcode::parse_program(SQUARE_3X).ok().unwrap();
+    let mut cpu = Cpu::with_overflow(&program, overflow);
+    cpu.add_input(input);
+    cpu.run_until_output()
+}
+
+#[test]
+fn checked_overflow() {
+    assert_eq!(square_3x(5i64, Overflow::Check), Ok(State::Output(390625)));
+    assert_eq!(square_3x(300i64, Overflow::Check),
+               Err(Error::Overflow { pc: 10, instr: 2 }));
+    assert_eq!(square_3x(300i64, Overflow::Wrap),
+               Ok(State::Output(300i64.wrapping_pow(8))));
+    assert_eq!(square_3x(300i128, Overflow::Check),
+               Ok(State::Output(300i128.pow(8))));
+    assert_eq!(square_3x(70000i128, Overflow::Check),
+               Err(Error::Overflow { pc: 10, instr: 2 }));
+}
+
+#[test]
+fn bignum_is_exact() {
+    let input: BigInt = "-70000".parse().unwrap();
+    let expected: BigInt = "576480100000000000000000000000000000000".parse().unwrap();
+    assert_eq!(square_3x(input, Overflow::Check), Ok(State::Output(expected)));
+}
+
+#[test]
+fn bignum_arithmetic() {
+    let big = |s: &str| s.parse::<BigInt>().unwrap();
+    let x = big("123456789012345678901234567890");
+    let y = big("-987654321098765432109876543210");
+    assert_eq!((&x + &y).to_string(), "-864197532086419753208641975320");
+    assert_eq!((&x * &y).to_string(),
+               "-121932631137021795226185032733622923332237463801111263526900");
+    assert_eq!((&y + &(-&y)).to_string(), "0");
+    assert!(y < x && -&x < x);
+    assert_eq!(BigInt::from(i128::MIN).to_i128(), Some(i128::MIN));
+    assert_eq!(big("170141183460469231731687303715884105728").to_i128(), None);
+}
+
+#[test]
+fn d09_on_every_word_type() {
+    let text = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/../d09.in")).unwrap();
+    fn boost<W: Word>(text: &str, mode: i64) -> Vec<W> {
+        let mut cpu = Cpu::new(&intcode::parse_program::<W>(text).ok().unwrap());
+        cpu.add_input(W::from_i64(mode));
+        cpu.run().unwrap();
+        cpu.outputs
+    }
+    assert_eq!(boost::<i64>(&text, 1), vec![2738720997]);
+    assert_eq!(boost::<BigInt>(&text, 2), vec![BigInt::from(50894)]);
+}